@@ -4,7 +4,7 @@
 use core::env;
 
 extern crate panic_itm;
-use cortex_m::{iprintln, iprint};
+use cortex_m::iprintln;
 
 use cortex_m_rt::entry;
 use embedded_hal::digital::v2::OutputPin;
@@ -136,7 +136,12 @@ fn main() -> ! {
         enc424j600::spi::interfaces::SPI_MODE,
         Hertz(enc424j600::spi::interfaces::SPI_CLOCK_FREQ),
         clocks);
-    let mut spi_eth = enc424j600::SpiEth::new(spi_eth_port, spi1_nss);
+    let mut spi_eth = match enc424j600::SpiEth::new(spi_eth_port, spi1_nss) {
+        Ok(spi_eth) => spi_eth,
+        Err(_) => {
+            panic!("Ethernet SPI port setup failed!")
+        }
+    };
     // Init
     match spi_eth.init_dev(&mut delay) {
         Ok(_) => {
@@ -153,21 +158,19 @@ fn main() -> ! {
     iprintln!(stim0, "Timer initialized");
 
     // Read MAC
-    let mut eth_mac_addr: [u8; 6] = [0; 6];
-    spi_eth.read_from_mac(&mut eth_mac_addr);
-    for i in 0..6 {
-        let byte = eth_mac_addr[i];
-        match i {
-            0 => iprint!(stim0, "MAC Address = {:02x}-", byte),
-            1..=4 => iprint!(stim0, "{:02x}-", byte),
-            5 => iprint!(stim0, "{:02x}\n", byte),
-            _ => ()
-        };
-    }
+    let eth_mac_addr = match spi_eth.read_mac_address() {
+        Ok(mac) => mac,
+        Err(_) => {
+            panic!("MAC address read failed!")
+        }
+    };
+    iprintln!(stim0, "MAC Address = {}", eth_mac_addr);
 
     // Init Rx/Tx buffers
     spi_eth.init_rxbuf();
     spi_eth.init_txbuf();
+    // Verify RX checksums on the controller instead of in smoltcp
+    spi_eth.set_rx_checksum_offload(true);
 
     // Copied / modified from smoltcp:
     // examples/loopback.rs, examples/multicast.rs
@@ -178,7 +181,7 @@ fn main() -> ! {
         arg_ip[0], arg_ip[1], arg_ip[2], arg_ip[3]), arg_ip_pref);
     let mut ip_addrs = [ip_addr];
     let mut iface = EthernetInterfaceBuilder::new(device)
-            .ethernet_addr(EthernetAddress(eth_mac_addr))
+            .ethernet_addr(EthernetAddress(eth_mac_addr.into()))
             .neighbor_cache(neighbor_cache)
             .ip_addrs(&mut ip_addrs[..])
             .finalize();
@@ -61,7 +61,12 @@ fn main() -> ! {
         enc424j600::spi::interfaces::SPI_MODE,
         Hertz(enc424j600::spi::interfaces::SPI_CLOCK_FREQ),
         clocks);
-    let mut spi_eth = enc424j600::SpiEth::new(spi_eth_port, spi1_nss);
+    let mut spi_eth = match enc424j600::SpiEth::new(spi_eth_port, spi1_nss) {
+        Ok(spi_eth) => spi_eth,
+        Err(_) => {
+            panic!("Ethernet SPI port setup failed!")
+        }
+    };
     // Init
     match spi_eth.init_dev(&mut delay) {
         Ok(_) => {
@@ -73,17 +78,13 @@ fn main() -> ! {
     }
 
     // Read MAC
-    let mut eth_mac_addr: [u8; 6] = [0; 6];
-    spi_eth.read_from_mac(&mut eth_mac_addr);
-    for i in 0..6 {
-        let byte = eth_mac_addr[i];
-        match i {
-            0 => iprint!(stim0, "MAC Address = {:02x}-", byte),
-            1..=4 => iprint!(stim0, "{:02x}-", byte),
-            5 => iprint!(stim0, "{:02x}\n", byte),
-            _ => ()
-        };
-    }
+    let eth_mac_addr = match spi_eth.read_mac_address() {
+        Ok(mac) => mac,
+        Err(_) => {
+            panic!("MAC address read failed!")
+        }
+    };
+    iprintln!(stim0, "MAC Address = {}", eth_mac_addr);
 
     // Init Rx/Tx buffers
     spi_eth.init_rxbuf();
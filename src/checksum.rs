@@ -0,0 +1,137 @@
+/// EtherTypes and IP protocol numbers with checksums handled by the driver
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const PROTOCOL_ICMP: u8 = 1;
+const PROTOCOL_TCP: u8 = 6;
+const PROTOCOL_UDP: u8 = 17;
+const PROTOCOL_ICMPV6: u8 = 58;
+
+/// Length of the Ethernet header, without VLAN tag
+const ETHERNET_HEADER_LENGTH: usize = 14;
+const IPV6_HEADER_LENGTH: usize = 40;
+
+/// Leading bytes of a frame that `checksum_regions_in` reads at most:
+/// the Ethernet header, the longest IPv4 header and a UDP header
+pub const HEADERS_LENGTH_MAX: usize = ETHERNET_HEADER_LENGTH + 60 + 8;
+
+/// An Internet checksum field of a frame, and the bytes it covers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChecksumRegion {
+    /// Offset in the frame of the covered bytes
+    pub offset: usize,
+    /// Number of covered bytes
+    pub len: usize,
+    /// Offset in the frame of the 16-bit checksum field, inside the covered bytes
+    pub field: usize,
+    /// Checksum of the pseudo-header, to seed the checksum of the covered bytes
+    pub seed: Option<u16>,
+    /// UDP: a computed checksum of 0x0000 is sent as 0xffff, and 0x0000 means none
+    pub udp: bool,
+}
+
+/// Internet checksum of `data`: the ones' complement of the ones' complement sum of
+/// its big-endian words, an odd trailing byte padded with zero.
+/// `seed` continues a previous checksum, as DMACSSD does on the chip.
+pub fn checksum(data: &[u8], seed: Option<u16>) -> u16 {
+    let mut sum = seed.map_or(0, |seed| !seed as u32);
+    for word in data.chunks(2) {
+        sum += (word[0] as u32) << 8 | *word.get(1).unwrap_or(&0) as u32;
+    }
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Checksum fields of an IPv4 or IPv6 frame: the IPv4 header checksum, then the
+/// TCP, UDP, ICMP or ICMPv6 checksum. Fragments, IPv6 extension headers and
+/// malformed lengths are left out.
+pub fn checksum_regions(frame: &[u8]) -> [Option<ChecksumRegion>; 2] {
+    checksum_regions_in(frame, frame.len()).0
+}
+
+/// Checksum fields of a `frame_len`-byte frame, given only its first bytes in `headers`,
+/// e.g. while the rest of it is still in the RX ring; see `checksum_regions`.
+/// The headers are found in the first `HEADERS_LENGTH_MAX` bytes.
+/// The flag is false if the frame is an IP frame carrying a checksum that was left out.
+pub fn checksum_regions_in(headers: &[u8], frame_len: usize)
+                           -> ([Option<ChecksumRegion>; 2], bool) {
+    let mut regions = [None; 2];
+    if headers.len() < ETHERNET_HEADER_LENGTH || frame_len < headers.len() {
+        return (regions, true)
+    }
+    let ip = &headers[ETHERNET_HEADER_LENGTH..];
+    let ip_len = frame_len - ETHERNET_HEADER_LENGTH;
+    match u16::from_be_bytes([headers[12], headers[13]]) {
+        ETHERTYPE_IPV4 if ip.len() >= 20 => {
+            let header_len = ((ip[0] & 0x0f) as usize) * 4;
+            let total_len = u16::from_be_bytes([ip[2], ip[3]]) as usize;
+            if header_len < 20 || total_len < header_len || total_len > ip_len {
+                return (regions, false)
+            }
+            regions[0] = Some(ChecksumRegion {
+                offset: ETHERNET_HEADER_LENGTH,
+                len: header_len,
+                field: ETHERNET_HEADER_LENGTH + 10,
+                seed: None,
+                udp: false,
+            });
+            // More fragments (MF) or a fragment offset
+            let fragmented = u16::from_be_bytes([ip[6], ip[7]]) & 0x3fff != 0;
+            if fragmented {
+                return (regions, false)
+            }
+            let payload_len = total_len - header_len;
+            let mut pseudo = [0; 12];
+            pseudo[..8].copy_from_slice(&ip[12..20]);
+            pseudo[9] = ip[9];
+            pseudo[10..].copy_from_slice(&(payload_len as u16).to_be_bytes());
+            regions[1] = transport_region(ip[9], ETHERNET_HEADER_LENGTH + header_len,
+                                          payload_len, &pseudo);
+            // Other protocols carry no checksum known to the driver
+            let complete = regions[1].is_some() ||
+                ![PROTOCOL_TCP, PROTOCOL_UDP, PROTOCOL_ICMP].contains(&ip[9]);
+            (regions, complete)
+        },
+        ETHERTYPE_IPV6 if ip.len() >= IPV6_HEADER_LENGTH => {
+            let payload_len = u16::from_be_bytes([ip[4], ip[5]]) as usize;
+            if IPV6_HEADER_LENGTH + payload_len > ip_len {
+                return (regions, false)
+            }
+            let mut pseudo = [0; 40];
+            pseudo[..32].copy_from_slice(&ip[8..40]);
+            pseudo[32..36].copy_from_slice(&(payload_len as u32).to_be_bytes());
+            pseudo[39] = ip[6];
+            regions[1] = transport_region(ip[6], ETHERNET_HEADER_LENGTH + IPV6_HEADER_LENGTH,
+                                          payload_len, &pseudo);
+            // Extension headers may lead to any checksum
+            let complete = regions[1].is_some();
+            (regions, complete)
+        },
+        ETHERTYPE_IPV4 | ETHERTYPE_IPV6 => (regions, false),
+        _ => (regions, true),
+    }
+}
+
+/// Checksum field of a transport header at `offset`, if `protocol` has one
+fn transport_region(protocol: u8, offset: usize, len: usize, pseudo: &[u8])
+                    -> Option<ChecksumRegion> {
+    let (field, min_len, seed) = match protocol {
+        PROTOCOL_TCP => (16, 20, Some(checksum(pseudo, None))),
+        PROTOCOL_UDP => (6, 8, Some(checksum(pseudo, None))),
+        // ICMP for IPv4 has no pseudo-header
+        PROTOCOL_ICMP if pseudo.len() == 12 => (2, 4, None),
+        PROTOCOL_ICMPV6 if pseudo.len() == 40 => (2, 4, Some(checksum(pseudo, None))),
+        _ => return None,
+    };
+    if len < min_len {
+        return None
+    }
+    Some(ChecksumRegion {
+        offset,
+        len,
+        field: offset + field,
+        seed,
+        udp: protocol == PROTOCOL_UDP,
+    })
+}
@@ -0,0 +1,25 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// Interrupt sources, with the same bit positions in EIE and EIR
+    /// See: EIE and EIR, ENC424J600 Data Sheet
+    pub struct Interrupts: u16 {
+        /// Link status changed (LINKIF)
+        const LINK = 1 << 11;
+        /// Packet received, PKTCNT is non-zero (PKTIF)
+        const PKT = 1 << 6;
+        /// DMA operation completed (DMAIF)
+        const DMA = 1 << 5;
+        /// Transmission completed (TXIF)
+        const TX = 1 << 3;
+        /// Transmission aborted (TXABTIF)
+        const TXABT = 1 << 2;
+        /// Receive aborted, RX buffer full (RXABTIF)
+        const RXABT = 1 << 1;
+        /// PKTCNT is full at 255 (PCFULIF)
+        const PCFUL = 1 << 0;
+    }
+}
+
+/// Global interrupt enable INTIE (EIE<15>)
+pub const INTIE: u16 = 1 << 15;
@@ -1,6 +1,7 @@
 #![no_std]
 
 pub mod spi;
+use core::fmt;
 use embedded_hal::{
     blocking::{
         spi::Transfer,
@@ -11,70 +12,612 @@ use embedded_hal::{
 
 pub mod rx;
 pub mod tx;
+pub mod phy;
+pub mod interrupt;
+pub mod checksum;
 
 #[cfg(feature="smoltcp")]
 pub mod smoltcp_phy;
 
+#[cfg(any(test, feature="sim"))]
+pub mod sim;
+
 /// Max raw frame array size
 pub const RAW_FRAME_LENGTH_MAX: usize = 0x1000;
 
+/// Ethernet MAC address
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MacAddress([u8; 6]);
+
+impl MacAddress {
+    pub const BROADCAST: MacAddress = MacAddress([0xff; 6]);
+
+    pub fn new(bytes: [u8; 6]) -> Self {
+        MacAddress(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 6] {
+        &self.0
+    }
+
+    /// Whether the I/G bit is set, i.e. the address is a multicast (or broadcast) address
+    pub fn is_multicast(&self) -> bool {
+        self.0[0] & 0x01 == 0x01
+    }
+
+    pub fn is_broadcast(&self) -> bool {
+        *self == Self::BROADCAST
+    }
+}
+
+impl From<MacAddress> for [u8; 6] {
+    fn from(mac: MacAddress) -> [u8; 6] {
+        mac.0
+    }
+}
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let b = &self.0;
+        write!(f, "{:02x}-{:02x}-{:02x}-{:02x}-{:02x}-{:02x}", b[0], b[1], b[2], b[3], b[4], b[5])
+    }
+}
+
 pub trait EthController<'c> {
-    fn init_dev(&mut self, delay: &mut dyn DelayUs<u16>) -> Result<(), EthControllerError>;
-    fn init_rxbuf(&mut self) -> Result<(), EthControllerError>;
-    fn init_txbuf(&mut self) -> Result<(), EthControllerError>;
-    fn receive_next(&mut self, is_poll: bool) -> Result<rx::RxPacket, EthControllerError>;
-    fn send_raw_packet(&mut self, packet: &tx::TxPacket) -> Result<(), EthControllerError>;
-    fn set_promiscuous(&mut self) -> Result<(), EthControllerError>;
-    fn read_from_mac(&mut self, mac: &mut [u8]) -> Result<(), EthControllerError>;
+    type Error;
+    fn init_dev(&mut self, delay: &mut dyn DelayUs<u16>) -> Result<(), Self::Error>;
+    fn init_rxbuf(&mut self) -> Result<(), Self::Error>;
+    fn init_txbuf(&mut self) -> Result<(), Self::Error>;
+    fn receive_next(&mut self, is_poll: bool) -> Result<rx::RxPacket, Self::Error>;
+    fn send_raw_packet(&mut self, packet: &tx::TxPacket) -> Result<tx::TxStatus, Self::Error>;
+    fn queue_packet(&mut self, packet: &tx::TxPacket) -> Result<(), Self::Error>;
+    fn service_tx_queue(&mut self) -> Result<Option<tx::TxStatus>, Self::Error>;
+    fn start_transmit(&mut self, packet: &tx::TxPacket) -> nb::Result<(), Self::Error>;
+    fn poll_transmit(&mut self) -> nb::Result<tx::TxStatus, Self::Error>;
+    fn dma_copy(&mut self, src: u16, dst: u16, len: u16) -> Result<(), Self::Error>;
+    fn start_dma_copy(&mut self, src: u16, dst: u16, len: u16) -> nb::Result<(), Self::Error>;
+    fn poll_dma(&mut self) -> nb::Result<(), Self::Error>;
+    fn dma_checksum(&mut self, addr: u16, len: u16, seed: Option<u16>) -> Result<u16, Self::Error>;
+    fn set_rx_checksum_offload(&mut self, enable: bool);
+    fn is_rx_checksum_offload(&self) -> bool;
+    fn set_promiscuous(&mut self) -> Result<(), Self::Error>;
+    fn set_rx_filter(&mut self, filter: rx::RxFilter) -> Result<(), Self::Error>;
+    fn read_rx_filter(&mut self) -> Result<rx::RxFilter, Self::Error>;
+    fn add_multicast(&mut self, addr: MacAddress) -> Result<(), Self::Error>;
+    fn remove_multicast(&mut self, addr: MacAddress) -> Result<(), Self::Error>;
+    fn clear_multicast(&mut self) -> Result<(), Self::Error>;
+    fn set_pattern_match(&mut self, offset: u16, pattern: &[u8], mask: u64,
+                         mode: rx::PatternMatchMode, inverted: bool) -> Result<(), Self::Error>;
+    fn enable_wake_on_lan(&mut self) -> Result<rx::RxFilter, Self::Error>;
+    fn read_mac_address(&mut self) -> Result<MacAddress, Self::Error>;
+    fn set_mac_address(&mut self, mac: MacAddress) -> Result<(), Self::Error>;
+    fn read_phy_reg(&mut self, addr: u8) -> Result<u16, Self::Error>;
+    fn write_phy_reg(&mut self, addr: u8, data: u16) -> Result<(), Self::Error>;
+    fn link_status(&mut self) -> Result<phy::LinkStatus, Self::Error>;
+    fn link_changed(&mut self) -> Result<bool, Self::Error>;
+    fn set_link_config(&mut self, config: phy::LinkConfig) -> Result<(), Self::Error>;
+    fn sync_mac_to_link(&mut self) -> Result<phy::LinkStatus, Self::Error>;
+    fn enable_interrupts(&mut self, interrupts: interrupt::Interrupts) -> Result<(), Self::Error>;
+    fn disable_interrupts(&mut self) -> Result<(), Self::Error>;
+    fn handle_interrupt(&mut self) -> Result<interrupt::Interrupts, Self::Error>;
+
+    fn read_phcon1(&mut self) -> Result<phy::Phcon1, Self::Error> {
+        Ok(phy::Phcon1::from_bits_truncate(self.read_phy_reg(phy::addrs::PHCON1)?))
+    }
+    fn write_phcon1(&mut self, phcon1: phy::Phcon1) -> Result<(), Self::Error> {
+        self.write_phy_reg(phy::addrs::PHCON1, phcon1.bits())
+    }
+    fn read_phstat1(&mut self) -> Result<phy::Phstat1, Self::Error> {
+        Ok(phy::Phstat1::from_bits_truncate(self.read_phy_reg(phy::addrs::PHSTAT1)?))
+    }
+    fn read_phana(&mut self) -> Result<phy::Phana, Self::Error> {
+        Ok(phy::Phana::from_bits_truncate(self.read_phy_reg(phy::addrs::PHANA)?))
+    }
+    fn write_phana(&mut self, phana: phy::Phana) -> Result<(), Self::Error> {
+        self.write_phy_reg(phy::addrs::PHANA, phana.bits())
+    }
+    fn read_phanlpa(&mut self) -> Result<phy::Phanlpa, Self::Error> {
+        Ok(phy::Phanlpa::from_bits_truncate(self.read_phy_reg(phy::addrs::PHANLPA)?))
+    }
+    fn read_phane(&mut self) -> Result<phy::Phane, Self::Error> {
+        Ok(phy::Phane::from_bits_truncate(self.read_phy_reg(phy::addrs::PHANE)?))
+    }
+    fn read_phstat2(&mut self) -> Result<phy::Phstat2, Self::Error> {
+        Ok(phy::Phstat2::from_bits_truncate(self.read_phy_reg(phy::addrs::PHSTAT2)?))
+    }
+    fn read_phstat3(&mut self) -> Result<phy::Phstat3, Self::Error> {
+        Ok(phy::Phstat3::from_bits_truncate(self.read_phy_reg(phy::addrs::PHSTAT3)?))
+    }
+}
+
+/// Errors returned by the controller, generic over the SPI and NSS pin error types
+#[derive(Debug)]
+pub enum EthControllerError<SpiE, PinE> {
+    /// SPI transfer failed while executing `opcode`, on register `addr` if the opcode takes one
+    Spi { opcode: u8, addr: Option<u8>, error: SpiE },
+    /// Driving the NSS pin failed
+    Pin(PinE),
+    /// EUDAST did not read back the expected value during the reset sequence
+    ResetVerification { expected: u16, found: u16 },
+    /// CLKRDY (ESTAT<12>) was not set within `PollLimits::clock_ready_us`
+    ClockReadyTimeout,
+    /// PKTIF (EIR<6>) was not set within `PollLimits::rx_polls`
+    RxTimeout,
+    /// TXRTS (ECON1<1>) was not cleared within `PollLimits::tx_polls`
+    TxTimeout,
+    /// BUSY (MISTAT<0>) was not cleared within `PollLimits::phy_polls`
+    PhyTimeout,
+    /// DMAST (ECON1<5>) was not cleared within `PollLimits::dma_polls`
+    DmaTimeout,
+    /// Frame of `len` bytes does not fit in the `max` bytes supported
+    FrameTooLarge { len: usize, max: usize },
+    /// Transmission was aborted by the MAC (TXABTIF), with the reported status
+    TxAborted(tx::TxStatus),
+    /// Incoming frames were dropped because the RX buffer was full (RXABTIF)
+    RxBufferOverflow,
+    /// Address cannot be used for the requested purpose,
+    /// e.g. a multicast station address or a unicast multicast group
+    InvalidMacAddress(MacAddress),
+    /// Pattern is longer than the 64-byte window, or the mask selects bytes past its end
+    InvalidPattern,
+    /// Buffer of `len` bytes is shorter than the `required` bytes
+    InvalidBufferLength { len: usize, required: usize },
+    /// No packet has been received (PKTIF not set)
+    NoRxPacket,
+    /// No transmission is in progress to poll for
+    NoTxPacket,
+    /// The TX buffer has no room for the frame until staged frames are sent
+    TxQueueFull,
+    /// Regions of the memory layout are misaligned, too small or do not fit in SRAM
+    InvalidMemoryLayout(MemoryLayout),
+    /// SRAM range of `len` bytes at `addr` is empty, or runs past the end of SRAM
+    /// outside of the RX ring, or is longer than the RX ring it wraps in
+    InvalidSramRange { addr: u16, len: u16 },
+}
+
+impl <SpiE: fmt::Debug, PinE: fmt::Debug> fmt::Display for EthControllerError<SpiE, PinE> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EthControllerError::Spi { opcode, addr: Some(addr), error } =>
+                write!(f, "SPI transfer failed (opcode {:#04x}, register {:#04x}): {:?}",
+                    opcode, addr, error),
+            EthControllerError::Spi { opcode, addr: None, error } =>
+                write!(f, "SPI transfer failed (opcode {:#04x}): {:?}", opcode, error),
+            EthControllerError::Pin(error) =>
+                write!(f, "NSS pin failed: {:?}", error),
+            EthControllerError::ResetVerification { expected, found } =>
+                write!(f, "Reset verification failed: EUDAST = {:#06x}, expected {:#06x}",
+                    found, expected),
+            EthControllerError::ClockReadyTimeout =>
+                write!(f, "Timed out waiting for CLKRDY"),
+            EthControllerError::RxTimeout =>
+                write!(f, "Timed out waiting for a received packet"),
+            EthControllerError::TxTimeout =>
+                write!(f, "Timed out waiting for transmission to complete"),
+            EthControllerError::PhyTimeout =>
+                write!(f, "Timed out waiting for PHY register access"),
+            EthControllerError::DmaTimeout =>
+                write!(f, "Timed out waiting for DMA operation"),
+            EthControllerError::FrameTooLarge { len, max } =>
+                write!(f, "Frame too large: {} bytes, max {} bytes", len, max),
+            EthControllerError::TxAborted(status) =>
+                write!(f, "Transmission aborted after {} collisions (ETXSTAT = {:#06x})",
+                    status.get_collision_count(), status.bits()),
+            EthControllerError::RxBufferOverflow =>
+                write!(f, "RX buffer overflow, frames dropped"),
+            EthControllerError::InvalidMacAddress(mac) =>
+                write!(f, "Invalid MAC address: {}", mac),
+            EthControllerError::InvalidPattern =>
+                write!(f, "Invalid pattern match window"),
+            EthControllerError::InvalidBufferLength { len, required } =>
+                write!(f, "Invalid buffer length: {} bytes, required {} bytes", len, required),
+            EthControllerError::NoRxPacket =>
+                write!(f, "No RX packet pending"),
+            EthControllerError::NoTxPacket =>
+                write!(f, "No TX packet pending"),
+            EthControllerError::TxQueueFull =>
+                write!(f, "TX queue full"),
+            EthControllerError::InvalidMemoryLayout(layout) =>
+                write!(f, "Invalid memory layout: TX {} bytes, user {} bytes",
+                    layout.tx_size, layout.user_size),
+            EthControllerError::InvalidSramRange { addr, len } =>
+                write!(f, "Invalid SRAM range: {} bytes at {:#06x}", len, addr),
+        }
+    }
+}
+
+/// Error type of `SpiEth` for the given SPI and NSS types
+pub type SpiEthError<SPI, NSS> =
+    EthControllerError<<SPI as Transfer<u8>>::Error, <NSS as OutputPin>::Error>;
+
+/// Budgets for the busy-wait loops in `SpiEth`
+/// Once a budget is used up, the call returns the matching timeout error
+#[derive(Clone, Copy, Debug)]
+pub struct PollLimits {
+    /// Time in microseconds to wait for CLKRDY in `init_dev`
+    pub clock_ready_us: u32,
+    /// Number of EIR reads to wait for PKTIF in `receive_next(true)`
+    pub rx_polls: u32,
+    /// Number of ECON1 reads to wait for TXRTS to clear in `send_raw_packet`
+    pub tx_polls: u32,
+    /// Number of MISTAT reads to wait for BUSY to clear on PHY register access
+    pub phy_polls: u32,
+    /// Number of ECON1 reads to wait for DMAST to clear in `dma_copy`
+    pub dma_polls: u32,
+}
+
+impl Default for PollLimits {
+    fn default() -> Self {
+        PollLimits {
+            clock_ready_us: 100_000,
+            rx_polls: 1_000_000,
+            tx_polls: 100_000,
+            phy_polls: 10_000,
+            dma_polls: 100_000,
+        }
+    }
 }
 
-/// TODO: Improve these error types
-pub enum EthControllerError {
-    SpiPortError,
-    GeneralError,
-    // TODO: Better name?
-    NoRxPacketError
+/// Split of the 24 KB SRAM, in order from address 0x0000:
+/// the TX region, an optional user data region and the RX ring up to the end of SRAM
+/// Sizes must be even, and the TX region and RX ring at least `MemoryLayout::MIN_REGION_SIZE`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryLayout {
+    /// Size in bytes of the TX region, starting at 0x0000
+    pub tx_size: u16,
+    /// Size in bytes of the user data region (EUDAST/EUDAND) following the TX region, 0 for none
+    pub user_size: u16,
 }
 
-impl From<spi::SpiPortError> for EthControllerError {
-    fn from(_: spi::SpiPortError) -> EthControllerError {
-        EthControllerError::SpiPortError
+impl Default for MemoryLayout {
+    fn default() -> Self {
+        MemoryLayout {
+            tx_size: tx::GPBUFEN_DEFAULT,
+            user_size: 0,
+        }
     }
 }
 
+impl MemoryLayout {
+    /// Room for one 1518-byte frame with its next packet pointer and RSV
+    pub const MIN_REGION_SIZE: u16 = 0x0600;
+    /// End of SRAM, exclusive
+    pub const SRAM_END: u32 = rx::RX_MAX_ADDRESS as u32 + 1;
+
+    /// End of the TX region, exclusive
+    pub fn get_tx_end(&self) -> u16 {
+        self.tx_size
+    }
+    /// First and last address of the user data region, if any
+    pub fn get_user_region(&self) -> Option<(u16, u16)> {
+        if self.user_size == 0 {
+            None
+        } else {
+            Some((self.tx_size, self.tx_size + self.user_size - 1))
+        }
+    }
+    /// Start of the RX ring (ERXST)
+    pub fn get_rx_start(&self) -> u16 {
+        self.tx_size + self.user_size
+    }
+    pub fn get_rx_size(&self) -> u16 {
+        (Self::SRAM_END - self.get_rx_start() as u32) as u16
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let rx_start = self.tx_size as u32 + self.user_size as u32;
+        self.tx_size & 1 == 0 &&
+            self.user_size & 1 == 0 &&
+            self.tx_size >= Self::MIN_REGION_SIZE &&
+            rx_start + Self::MIN_REGION_SIZE as u32 <= Self::SRAM_END
+    }
+}
+
+/// Back-to-back inter-packet gap for full and half duplex
+/// See: MABBIPG, ENC424J600 Data Sheet
+const MABBIPG_FULL_DUPLEX: u16 = 0x15;
+const MABBIPG_HALF_DUPLEX: u16 = 0x12;
+/// Non-back-to-back inter-packet gap recommended for both duplex modes
+const MAIPG_DEFAULT: u16 = 0x0c12;
+
+/// Interval in microseconds between CLKRDY polls
+const CLOCK_READY_POLL_US: u16 = 10;
+
 /// Ethernet controller using SPI interface
 pub struct SpiEth<SPI: Transfer<u8>,
                   NSS: OutputPin> {
     spi_port: spi::SpiPort<SPI, NSS>,
     rx_buf: rx::RxBuffer,
-    tx_buf: tx::TxBuffer
+    tx_buf: tx::TxBuffer,
+    poll_limits: PollLimits,
+    memory_layout: MemoryLayout,
+    link_config: phy::LinkConfig,
+    hash_table: rx::HashTable,
+    rx_error_policy: rx::RxErrorPolicy,
+    rx_error_counters: rx::RxErrorCounters,
+    rx_checksum_offload: bool
 }
 
 impl <SPI: Transfer<u8>,
       NSS: OutputPin> SpiEth<SPI, NSS> {
-    pub fn new(spi: SPI, nss: NSS) -> Self {
-        SpiEth {
-            spi_port: spi::SpiPort::new(spi, nss),
-            rx_buf: rx::RxBuffer::new(),
-            tx_buf: tx::TxBuffer::new()
+    pub fn new(spi: SPI, nss: NSS) -> Result<Self, SpiEthError<SPI, NSS>> {
+        Self::with_memory_layout(spi, nss, MemoryLayout::default())
+    }
+
+    /// Create a controller with the SRAM split according to `layout`,
+    /// applied to the hardware by `init_rxbuf`
+    pub fn with_memory_layout(spi: SPI, nss: NSS, layout: MemoryLayout)
+                              -> Result<Self, SpiEthError<SPI, NSS>> {
+        if !layout.is_valid() {
+            return Err(EthControllerError::InvalidMemoryLayout(layout))
+        }
+        let mut rx_buf = rx::RxBuffer::new();
+        rx_buf.set_wrap_addr(layout.get_rx_start());
+        let mut tx_buf = tx::TxBuffer::new();
+        tx_buf.set_end_addr(layout.get_tx_end());
+        Ok(SpiEth {
+            spi_port: spi::SpiPort::new(spi, nss)?,
+            rx_buf,
+            tx_buf,
+            poll_limits: PollLimits::default(),
+            memory_layout: layout,
+            link_config: phy::LinkConfig::default(),
+            hash_table: rx::HashTable::new(),
+            rx_error_policy: rx::RxErrorPolicy::default(),
+            rx_error_counters: rx::RxErrorCounters::default(),
+            rx_checksum_offload: false
+        })
+    }
+
+    pub fn set_poll_limits(&mut self, poll_limits: PollLimits) {
+        self.poll_limits = poll_limits;
+    }
+    pub fn get_poll_limits(&self) -> PollLimits {
+        self.poll_limits
+    }
+
+    pub fn get_memory_layout(&self) -> MemoryLayout {
+        self.memory_layout
+    }
+
+    /// Set how `receive_next` handles frames whose RSV reports an error
+    pub fn set_rx_error_policy(&mut self, policy: rx::RxErrorPolicy) {
+        self.rx_error_policy = policy;
+    }
+    pub fn get_rx_error_policy(&self) -> rx::RxErrorPolicy {
+        self.rx_error_policy
+    }
+
+    /// Frames discarded under `RxErrorPolicy::Drop` or by RX checksum offload
+    /// since the last reset
+    pub fn get_rx_error_counters(&self) -> rx::RxErrorCounters {
+        self.rx_error_counters
+    }
+    pub fn reset_rx_error_counters(&mut self) {
+        self.rx_error_counters = rx::RxErrorCounters::default();
+    }
+
+    /// Read the next packet from the RX ring and free its space
+    /// Ok(None) if the packet failed RX checksum offload and was dropped
+    fn read_next_packet(&mut self, is_poll: bool)
+                        -> Result<Option<rx::RxPacket>, SpiEthError<SPI, NSS>> {
+        // Poll PKTIF (EIR<6>) to check if it is set
+        let mut polls = 0;
+        loop {
+            let eir = self.spi_port.read_reg_16b(spi::addrs::EIR)?;
+            if eir & 0x40 == 0x40 { break }
+            polls += 1;
+            if is_poll && polls >= self.poll_limits.rx_polls {
+                return Err(EthControllerError::RxTimeout)
+            }
+            if !is_poll {
+                // Report frames dropped on RXABTIF (EIR<1>) once, then clear only it
+                if eir & 0x02 == 0x02 {
+                    self.spi_port.clear_bits_8b(spi::addrs::EIR, 0x02)?;
+                    return Err(EthControllerError::RxBufferOverflow)
+                }
+                return Err(EthControllerError::NoRxPacket)
+            }
+        }
+        // Set ERXRDPT pointer to next_addr
+        // RERXDATA wraps ERXRDPT from the end of SRAM back to ERXST, so the packet
+        // can be read in one pass wherever it sits in the ring
+        let packet_addr = self.rx_buf.get_next_addr();
+        self.spi_port.write_reg_16b(spi::addrs::ERXRDPT, packet_addr)?;
+        // Read 2 bytes to update next_addr
+        let mut next_addr_buf = [0; 3];
+        self.spi_port.read_rxdat(&mut next_addr_buf, 2)?;
+        self.rx_buf.set_next_addr((next_addr_buf[1] as u16) | ((next_addr_buf[2] as u16) << 8));
+        // Read 6 bytes to update rsv
+        let mut rsv_buf = [0; 7];
+        self.spi_port.read_rxdat(&mut rsv_buf, 6)?;
+        // Construct an RxPacket
+        // TODO: can we directly assign to fields instead of using functions?
+        let mut rx_packet = rx::RxPacket::new();
+        // Get and update frame length
+        rx_packet.write_to_rsv(&rsv_buf[1..]);
+        rx_packet.update_frame_length();
+        if rx_packet.get_frame_length() > RAW_FRAME_LENGTH_MAX {
+            // Skip over the frame so that the next one can still be read
+            self.free_rx_packet()?;
+            return Err(EthControllerError::FrameTooLarge {
+                len: rx_packet.get_frame_length(),
+                max: RAW_FRAME_LENGTH_MAX
+            })
+        }
+        let frame_length = rx_packet.get_frame_length();
+        let mut frame_buf = [0; RAW_FRAME_LENGTH_MAX + 1];
+        let mut header_length = 0;
+        // Frames that `receive_next` drops for their RSV are not worth verifying
+        let rsv_drop = self.rx_error_policy == rx::RxErrorPolicy::Drop &&
+            rx_packet.get_rsv_error().is_some();
+        if self.rx_checksum_offload && !rsv_drop {
+            // Read the headers only, and verify the checksums while the frame is in the ring
+            header_length = frame_length.min(checksum::HEADERS_LENGTH_MAX);
+            self.spi_port.read_rxdat(&mut frame_buf, header_length)?;
+            // The frame follows the next packet pointer (2 bytes) and the RSV (6 bytes)
+            let frame_addr = self.rx_buf.wrapping_add(packet_addr, 8);
+            let payload_length = rx_packet.get_payload_length();
+            let headers = &frame_buf[1..1 + header_length.min(payload_length)];
+            if let Some(error) = self.verify_rx_checksums(frame_addr, headers, payload_length)? {
+                self.rx_error_counters.count(error);
+                self.free_rx_packet()?;
+                return Ok(None)
+            }
+        }
+        // Read the rest of the frame bytes after the headers already read;
+        // the byte before them is overwritten by the opcode, so restore it
+        let last_header_byte = frame_buf[header_length];
+        self.spi_port.read_rxdat(&mut frame_buf[header_length..], frame_length - header_length)?;
+        frame_buf[header_length] = last_header_byte;
+        rx_packet.copy_frame_from(&frame_buf[1..]);
+        self.free_rx_packet()?;
+        // Return the RxPacket
+        Ok(Some(rx_packet))
+    }
+
+    /// Free the space of the packet just read from the RX ring
+    fn free_rx_packet(&mut self) -> Result<(), SpiEthError<SPI, NSS>> {
+        // Set ERXTAIL pointer to (next_addr - 2), wrapping against ERXST
+        self.rx_buf.set_tail_addr(self.rx_buf.tail_for(self.rx_buf.get_next_addr()));
+        self.spi_port.write_reg_16b(spi::addrs::ERXTAIL, self.rx_buf.get_tail_addr())?;
+        // Set PKTDEC (ECON1<88>) to decrement PKTCNT
+        let econ1_hi = self.spi_port.read_reg_8b(spi::addrs::ECON1 + 1)?;
+        self.spi_port.write_reg_8b(spi::addrs::ECON1 + 1, 0x01 | (econ1_hi & 0xfe))?;
+        Ok(())
+    }
+
+    /// Verify the checksums of the frame at `frame_addr` in the RX ring with the DMA engine,
+    /// given its first bytes in `headers`: summing over a correct checksum field gives zero
+    /// Returns the reason to drop the frame, if any.
+    fn verify_rx_checksums(&mut self, frame_addr: u16, headers: &[u8], frame_length: usize)
+                           -> Result<Option<rx::RxFrameError>, SpiEthError<SPI, NSS>> {
+        let (regions, complete) = checksum::checksum_regions_in(headers, frame_length);
+        if !complete {
+            return Ok(Some(rx::RxFrameError::ChecksumUnverified))
+        }
+        for region in regions.iter().flatten() {
+            // A UDP checksum field of zero means the sender computed none
+            if region.udp && headers[region.field..region.field + 2] == [0, 0] {
+                continue
+            }
+            let addr = self.rx_buf.wrapping_add(frame_addr, region.offset as u16);
+            if self.dma_checksum(addr, region.len as u16, region.seed)? != 0 {
+                return Ok(Some(rx::RxFrameError::ChecksumError))
+            }
+        }
+        Ok(None)
+    }
+
+    /// Hand the oldest staged frame to the MAC: set ETXST/ETXLEN and TXRTS
+    fn start_next_transmission(&mut self) -> Result<(), SpiEthError<SPI, NSS>> {
+        let (start, len) = match self.tx_buf.front() {
+            Some(frame) => frame,
+            None => return Ok(()),
+        };
+        // Set ETXST to packet start address
+        self.spi_port.write_reg_16b(spi::addrs::ETXST, start)?;
+        // Set ETXLEN to packet length
+        self.spi_port.write_reg_16b(spi::addrs::ETXLEN, len)?;
+        // Set TXRTS (ECON1<1>) to start transmission
+        self.spi_port.set_bits_8b(spi::addrs::ECON1, 0x02)?;
+        self.tx_buf.set_transmitting(true);
+        Ok(())
+    }
+
+    /// Write the word of EHT1-EHT4 holding `bit` from the hash table
+    fn write_hash_table_word(&mut self, bit: u8) -> Result<(), SpiEthError<SPI, NSS>> {
+        let addr = spi::addrs::EHT1 + 2 * (bit / 16);
+        self.spi_port.write_reg_16b(addr, self.hash_table.word(bit))?;
+        Ok(())
+    }
+
+    /// Check that `len` bytes at `addr` are in SRAM; a range starting in the RX ring
+    /// may wrap from the end of SRAM to ERXST, as the DMA pointers do
+    fn check_sram_range(&self, addr: u16, len: u16) -> Result<(), SpiEthError<SPI, NSS>> {
+        let rx_start = self.memory_layout.get_rx_start();
+        let end = addr as u32 + len as u32;
+        let valid = len > 0 && addr as u32 <= rx::RX_MAX_ADDRESS as u32 && if addr >= rx_start {
+            len <= self.memory_layout.get_rx_size()
+        } else {
+            end <= MemoryLayout::SRAM_END
+        };
+        if valid { Ok(()) } else { Err(EthControllerError::InvalidSramRange { addr, len }) }
+    }
+
+    /// Start a DMA operation over `len` bytes at `src` with the given
+    /// DMACPY (ECON1<4>), DMACSSD (ECON1<3>) and DMANOCS (ECON1<2>) settings
+    /// Returns WouldBlock if DMAST (ECON1<5>) is still set from a previous operation.
+    fn start_dma(&mut self, src: u16, dst: u16, len: u16, econ1_mode: u8)
+                 -> nb::Result<(), SpiEthError<SPI, NSS>> {
+        let econ1_lo = self.spi_port.read_reg_8b(spi::addrs::ECON1)?;
+        if econ1_lo & 0x20 != 0 {
+            return Err(nb::Error::WouldBlock)
+        }
+        self.spi_port.write_reg_16b(spi::addrs::EDMAST, src)?;
+        self.spi_port.write_reg_16b(spi::addrs::EDMALEN, len)?;
+        self.spi_port.write_reg_16b(spi::addrs::EDMADST, dst)?;
+        // Use BFCU/BFSU so that TXRTS (ECON1<1>) is left untouched
+        self.spi_port.clear_bits_8b(spi::addrs::ECON1, 0x1c & !econ1_mode)?;
+        if econ1_mode != 0 {
+            self.spi_port.set_bits_8b(spi::addrs::ECON1, econ1_mode)?;
+        }
+        // Set DMAST (ECON1<5>) to start the operation
+        self.spi_port.set_bits_8b(spi::addrs::ECON1, 0x20)?;
+        Ok(())
+    }
+
+    /// Wait for DMAST (ECON1<5>) to clear, within `PollLimits::dma_polls` reads
+    fn wait_dma_done(&mut self) -> Result<(), SpiEthError<SPI, NSS>> {
+        let mut polls = 0;
+        loop {
+            let econ1_lo = self.spi_port.read_reg_8b(spi::addrs::ECON1)?;
+            if econ1_lo & 0x20 == 0 { break }
+            polls += 1;
+            if polls >= self.poll_limits.dma_polls {
+                return Err(EthControllerError::DmaTimeout)
+            }
         }
+        Ok(())
+    }
+
+    /// Poll BUSY (MISTAT<0>) until the MII management operation completes
+    fn wait_phy_ready(&mut self) -> Result<(), SpiEthError<SPI, NSS>> {
+        let mut polls = 0;
+        loop {
+            let mistat = self.spi_port.read_reg_8b(spi::addrs::MISTAT)?;
+            if mistat & 0x01 == 0 { break }
+            polls += 1;
+            if polls >= self.poll_limits.phy_polls {
+                return Err(EthControllerError::PhyTimeout)
+            }
+        }
+        Ok(())
     }
 }
 
 impl <'c, SPI: Transfer<u8>,
       NSS: OutputPin> EthController<'c> for SpiEth<SPI, NSS> {
-    fn init_dev(&mut self, delay: &mut dyn DelayUs<u16>) -> Result<(), EthControllerError> {
+    type Error = SpiEthError<SPI, NSS>;
+
+    fn init_dev(&mut self, delay: &mut dyn DelayUs<u16>) -> Result<(), Self::Error> {
         // Write 0x1234 to EUDAST
         self.spi_port.write_reg_16b(spi::addrs::EUDAST, 0x1234)?;
         // Verify that EUDAST is 0x1234
         let mut eudast = self.spi_port.read_reg_16b(spi::addrs::EUDAST)?;
         if eudast != 0x1234 {
-            return Err(EthControllerError::GeneralError)
+            return Err(EthControllerError::ResetVerification { expected: 0x1234, found: eudast })
         }
         // Poll CLKRDY (ESTAT<12>) to check if it is set
+        let mut waited_us = 0;
         loop {
             let estat = self.spi_port.read_reg_16b(spi::addrs::ESTAT)?;
             if estat & 0x1000 == 0x1000 { break }
+            if waited_us >= self.poll_limits.clock_ready_us {
+                return Err(EthControllerError::ClockReadyTimeout)
+            }
+            delay.delay_us(CLOCK_READY_POLL_US);
+            waited_us += CLOCK_READY_POLL_US as u32;
         }
         // Set ETHRST (ECON2<4>) to 1
         let econ2 = self.spi_port.read_reg_8b(spi::addrs::ECON2)?;
@@ -84,18 +627,29 @@ impl <'c, SPI: Transfer<u8>,
         // Verify that EUDAST is 0x0000
         eudast = self.spi_port.read_reg_16b(spi::addrs::EUDAST)?;
         if eudast != 0x0000 {
-            return Err(EthControllerError::GeneralError)
+            return Err(EthControllerError::ResetVerification { expected: 0x0000, found: eudast })
         }
         // Wait for 256us
         delay.delay_us(256_u16);
+        // Configure the PHY link
+        self.set_link_config(self.link_config)?;
         Ok(())
     }
 
-    fn init_rxbuf(&mut self) -> Result<(), EthControllerError> {
-        // Set ERXST pointer
-        self.spi_port.write_reg_16b(spi::addrs::ERXST, self.rx_buf.get_wrap_addr())?;
-        // Set ERXTAIL pointer
+    fn init_rxbuf(&mut self) -> Result<(), Self::Error> {
+        // Set ERXST pointer; the first packet is written there
+        let wrap_addr = self.rx_buf.get_wrap_addr();
+        self.spi_port.write_reg_16b(spi::addrs::ERXST, wrap_addr)?;
+        self.rx_buf.set_next_addr(wrap_addr);
+        // Set ERXTAIL pointer, leaving the whole ring free
+        self.rx_buf.set_tail_addr(self.rx_buf.tail_for(wrap_addr));
         self.spi_port.write_reg_16b(spi::addrs::ERXTAIL, self.rx_buf.get_tail_addr())?;
+        // Set EUDAST and EUDAND to the user data region,
+        // or past the end of SRAM if there is none
+        let (user_start, user_end) = self.memory_layout.get_user_region()
+            .unwrap_or((rx::RX_MAX_ADDRESS + 1, rx::RX_MAX_ADDRESS + 2));
+        self.spi_port.write_reg_16b(spi::addrs::EUDAST, user_start)?;
+        self.spi_port.write_reg_16b(spi::addrs::EUDAND, user_end)?;
         // Set MAMXFL to maximum number of bytes in each accepted packet
         self.spi_port.write_reg_16b(spi::addrs::MAMXFL, RAW_FRAME_LENGTH_MAX as u16)?;
         // Enable RXEN (ECON1<0>)
@@ -104,87 +658,238 @@ impl <'c, SPI: Transfer<u8>,
         Ok(())
     }
 
-    fn init_txbuf(&mut self) -> Result<(), EthControllerError> {
+    fn init_txbuf(&mut self) -> Result<(), Self::Error> {
+        // Drop any staged frames
+        self.tx_buf.reset();
         // Set EGPWRPT pointer
-        self.spi_port.write_reg_16b(spi::addrs::EGPWRPT, 0x0000)?;
+        self.spi_port.write_reg_16b(spi::addrs::EGPWRPT, self.tx_buf.get_wrap_addr())?;
         Ok(())
     }
 
     /// Receive the next packet and return it
-    /// Set is_poll to true for returning until PKTIF is set;
+    /// Set is_poll to true for returning until PKTIF is set,
+    /// or Err after `PollLimits::rx_polls` reads;
     /// Set is_poll to false for returning Err when PKTIF is not set
-    fn receive_next(&mut self, is_poll: bool) -> Result<rx::RxPacket, EthControllerError> {
-        // Poll PKTIF (EIR<4>) to check if it is set
+    /// Frames with an RSV error are handled according to the `RxErrorPolicy`
+    fn receive_next(&mut self, is_poll: bool) -> Result<rx::RxPacket, Self::Error> {
         loop {
-            let eir = self.spi_port.read_reg_16b(spi::addrs::EIR)?;
-            if eir & 0x40 == 0x40 { break }
-            if !is_poll {
-                return Err(EthControllerError::NoRxPacketError)
+            let mut rx_packet = match self.read_next_packet(is_poll)? {
+                Some(rx_packet) => rx_packet,
+                None => continue,
+            };
+            match (self.rx_error_policy, rx_packet.get_rsv_error()) {
+                (rx::RxErrorPolicy::Drop, Some(error)) => self.rx_error_counters.count(error),
+                (rx::RxErrorPolicy::Flag, error) => {
+                    rx_packet.set_error(error);
+                    return Ok(rx_packet)
+                },
+                _ => return Ok(rx_packet),
             }
         }
-        // Set ERXRDPT pointer to next_addr
-        self.spi_port.write_reg_16b(spi::addrs::ERXRDPT, self.rx_buf.get_next_addr())?;
-        // Read 2 bytes to update next_addr
-        let mut next_addr_buf = [0; 3];
-        self.spi_port.read_rxdat(&mut next_addr_buf, 2)?;
-        self.rx_buf.set_next_addr((next_addr_buf[1] as u16) | ((next_addr_buf[2] as u16) << 8));
-        // Read 6 bytes to update rsv
-        let mut rsv_buf = [0; 7];
-        self.spi_port.read_rxdat(&mut rsv_buf, 6)?;
-        // Construct an RxPacket
-        // TODO: can we directly assign to fields instead of using functions?
-        let mut rx_packet = rx::RxPacket::new();
-        // Get and update frame length
-        rx_packet.write_to_rsv(&rsv_buf[1..]);
-        rx_packet.update_frame_length();
-        // Read frame bytes
-        let mut frame_buf = [0; RAW_FRAME_LENGTH_MAX];
-        self.spi_port.read_rxdat(&mut frame_buf, rx_packet.get_frame_length())?;
-        rx_packet.copy_frame_from(&frame_buf[1..]);
-        // Set ERXTAIL pointer to (next_addr - 2)
-        if self.rx_buf.get_next_addr() > rx::ERXST_DEFAULT {
-            self.spi_port.write_reg_16b(spi::addrs::ERXTAIL, self.rx_buf.get_next_addr() - 2)?;
-        } else {
-            self.spi_port.write_reg_16b(spi::addrs::ERXTAIL, rx::RX_MAX_ADDRESS - 1)?;
+    }
+
+    /// Send an established packet and wait for it to leave the MAC
+    /// Frames queued earlier with `queue_packet` are sent first; their status, aborts
+    /// included, is not reported. If the TX queue is full, this waits for room first.
+    fn send_raw_packet(&mut self, packet: &tx::TxPacket) -> Result<tx::TxStatus, Self::Error> {
+        // Poll for room in the queue, completing earlier frames
+        let mut polls = 0;
+        loop {
+            match self.queue_packet(packet) {
+                Ok(()) => break,
+                Err(EthControllerError::TxQueueFull) => (),
+                Err(e) => return Err(e),
+            }
+            match self.service_tx_queue() {
+                Ok(_) | Err(EthControllerError::TxAborted(_)) => (),
+                Err(e) => return Err(e),
+            }
+            polls += 1;
+            if polls >= self.poll_limits.tx_polls {
+                return Err(EthControllerError::TxTimeout)
+            }
+        }
+        // Poll until the queue, ending with this packet, is drained
+        loop {
+            match self.service_tx_queue() {
+                // This packet is the last one to complete
+                Ok(Some(status)) if self.tx_buf.is_empty() => return Ok(status),
+                Err(EthControllerError::TxAborted(status)) if self.tx_buf.is_empty() =>
+                    return Err(EthControllerError::TxAborted(status)),
+                // An earlier frame was aborted; keep going with the rest
+                Ok(_) | Err(EthControllerError::TxAborted(_)) => (),
+                Err(e) => return Err(e),
+            }
+            polls += 1;
+            if polls >= self.poll_limits.tx_polls {
+                return Err(EthControllerError::TxTimeout)
+            }
         }
-        // Set PKTDEC (ECON1<88>) to decrement PKTCNT
-        let econ1_hi = self.spi_port.read_reg_8b(spi::addrs::ECON1 + 1)?;
-        self.spi_port.write_reg_8b(spi::addrs::ECON1 + 1, 0x01 | (econ1_hi & 0xfe))?;
-        // Return the RxPacket
-        Ok(rx_packet)
     }
 
-    /// Send an established packet
-    fn send_raw_packet(&mut self, packet: &tx::TxPacket) -> Result<(), EthControllerError> {
-        // Set EGPWRPT pointer to next_addr
-        self.spi_port.write_reg_16b(spi::addrs::EGPWRPT, self.tx_buf.get_next_addr())?;
+    /// Stage a packet in the TX buffer, and start sending it if the MAC is idle
+    /// With `TxPacket::set_checksum_offload`, its checksums are computed and patched in first.
+    /// The packet is written contiguously, wrapping to the start of the TX region
+    /// instead of crossing its end; Err(TxQueueFull) if staged frames leave no room.
+    fn queue_packet(&mut self, packet: &tx::TxPacket) -> Result<(), Self::Error> {
+        let len = packet.get_frame_length();
+        let region = (self.tx_buf.get_end_addr() - self.tx_buf.get_wrap_addr()) as usize;
+        if len > region {
+            return Err(EthControllerError::FrameTooLarge { len, max: region })
+        }
+        let start = self.tx_buf.allocate(len as u16).ok_or(EthControllerError::TxQueueFull)?;
+        let regions = if packet.is_checksum_offload() {
+            checksum::checksum_regions(&packet.get_frame()[..len])
+        } else {
+            [None; 2]
+        };
+        // Set EGPWRPT pointer to the packet start address
+        self.spi_port.write_reg_16b(spi::addrs::EGPWRPT, start)?;
         // Copy packet data to SRAM Buffer
         // 1-byte Opcode is included
         let mut txdat_buf: [u8; RAW_FRAME_LENGTH_MAX + 1] = [0; RAW_FRAME_LENGTH_MAX + 1];
         packet.write_frame_to(&mut txdat_buf[1..]);
-        self.spi_port.write_txdat(&mut txdat_buf, packet.get_frame_length())?;
-        // Set ETXST to packet start address
-        self.spi_port.write_reg_16b(spi::addrs::ETXST, self.tx_buf.get_next_addr())?;
-        // Set ETXLEN to packet length
-        self.spi_port.write_reg_16b(spi::addrs::ETXLEN, packet.get_frame_length() as u16)?;
-        // Set TXRTS (ECON1<1>) to start transmission
-        let mut econ1_lo = self.spi_port.read_reg_8b(spi::addrs::ECON1)?;
-        self.spi_port.write_reg_8b(spi::addrs::ECON1, 0x02 | (econ1_lo & 0xfd))?;
-        // Poll TXRTS (ECON1<1>) to check if it is reset
-        loop {
-            econ1_lo = self.spi_port.read_reg_8b(spi::addrs::ECON1)?;
-            if econ1_lo & 0x02 == 0 { break }
+        // Checksum fields must be zero while the DMA engine sums over them
+        for region in regions.iter().flatten() {
+            txdat_buf[1 + region.field..][..2].copy_from_slice(&[0, 0]);
+        }
+        self.spi_port.write_txdat(&mut txdat_buf, len)?;
+        // Patch in the checksums computed over the staged frame
+        for region in regions.iter().flatten() {
+            let mut sum = self.dma_checksum(start + region.offset as u16, region.len as u16,
+                                            region.seed)?;
+            if region.udp && sum == 0 {
+                sum = 0xffff;
+            }
+            self.spi_port.write_reg_16b(spi::addrs::EGPWRPT, start + region.field as u16)?;
+            let mut sum_buf = [0; 3];
+            sum_buf[1..].copy_from_slice(&sum.to_be_bytes());
+            self.spi_port.write_txdat(&mut sum_buf, 2)?;
+        }
+        self.tx_buf.push(start, len as u16);
+        if !self.tx_buf.is_transmitting() {
+            self.start_next_transmission()?;
         }
-        // TODO: Read ETXSTAT to understand Ethernet transmission status
-        // (See: Register 9-2, ENC424J600 Data Sheet)
-        // Update TX buffer start address
-        self.tx_buf.set_next_addr((self.tx_buf.get_next_addr() + packet.get_frame_length() as u16) %
-            tx::GPBUFEN_DEFAULT);
         Ok(())
     }
 
+    /// Complete the frame on the wire once TXRTS has cleared, and start the next staged one
+    /// Call on TXIF/TXABTIF, or poll. Returns the status of the completed frame,
+    /// None if it is still being sent, or Err(TxAborted) if the MAC gave up on it.
+    fn service_tx_queue(&mut self) -> Result<Option<tx::TxStatus>, Self::Error> {
+        if !self.tx_buf.is_transmitting() {
+            return Ok(None)
+        }
+        // Check if TXRTS (ECON1<1>) is reset
+        let econ1_lo = self.spi_port.read_reg_8b(spi::addrs::ECON1)?;
+        if econ1_lo & 0x02 != 0 {
+            return Ok(None)
+        }
+        // Read ETXSTAT and ETXWIRE for the transmission status
+        // (See: ETXSTAT, ENC424J600 Data Sheet)
+        let status = tx::TxStatus::from_bits(
+            self.spi_port.read_reg_16b(spi::addrs::ETXSTAT)?,
+            self.spi_port.read_reg_16b(spi::addrs::ETXWIRE)?);
+        self.tx_buf.pop();
+        // Decide on ETXSTAT rather than TXABTIF (EIR<2>), which `handle_interrupt`
+        // may already have cleared; clear the flag for polled use
+        let aborted = status.is_aborted();
+        if aborted {
+            self.spi_port.clear_bits_8b(spi::addrs::EIR, 0x04)?;
+        }
+        // Kick the next staged frame
+        self.start_next_transmission()?;
+        if aborted {
+            return Err(EthControllerError::TxAborted(status))
+        }
+        Ok(Some(status))
+    }
+
+    /// Start sending a packet without waiting for the wire
+    /// Returns as soon as the packet is staged and, if the MAC is idle, TXRTS is set;
+    /// WouldBlock while the TX queue is full.
+    fn start_transmit(&mut self, packet: &tx::TxPacket) -> nb::Result<(), Self::Error> {
+        match self.queue_packet(packet) {
+            Err(EthControllerError::TxQueueFull) => Err(nb::Error::WouldBlock),
+            result => result.map_err(nb::Error::Other),
+        }
+    }
+
+    /// Poll for the completion of the oldest transmission started with `start_transmit`
+    /// WouldBlock while it is on the wire; Err(NoTxPacket) if nothing is being sent.
+    fn poll_transmit(&mut self) -> nb::Result<tx::TxStatus, Self::Error> {
+        if !self.tx_buf.is_transmitting() {
+            return Err(nb::Error::Other(EthControllerError::NoTxPacket))
+        }
+        match self.service_tx_queue()? {
+            Some(status) => Ok(status),
+            None => Err(nb::Error::WouldBlock),
+        }
+    }
+
+    /// Copy `len` bytes of SRAM from `src` to `dst` with the DMA engine, and wait for it
+    /// Either range may wrap from the end of SRAM to ERXST if it starts in the RX ring.
+    fn dma_copy(&mut self, src: u16, dst: u16, len: u16) -> Result<(), Self::Error> {
+        self.wait_dma_done()?;
+        nb::block!(self.start_dma_copy(src, dst, len))?;
+        self.wait_dma_done()
+    }
+
+    /// Start copying `len` bytes of SRAM from `src` to `dst` with the DMA engine
+    /// WouldBlock while a previous DMA operation is in progress.
+    fn start_dma_copy(&mut self, src: u16, dst: u16, len: u16) -> nb::Result<(), Self::Error> {
+        self.check_sram_range(src, len)?;
+        self.check_sram_range(dst, len)?;
+        // Set DMACPY (ECON1<4>) and DMANOCS (ECON1<2>): copy without computing a checksum
+        self.start_dma(src, dst, len, 0x14)
+    }
+
+    /// Poll for the completion of the DMA operation in progress
+    fn poll_dma(&mut self) -> nb::Result<(), Self::Error> {
+        // Check if DMAST (ECON1<5>) is reset
+        let econ1_lo = self.spi_port.read_reg_8b(spi::addrs::ECON1)?;
+        if econ1_lo & 0x20 != 0 {
+            return Err(nb::Error::WouldBlock)
+        }
+        Ok(())
+    }
+
+    /// Compute the Internet checksum of `len` bytes of SRAM at `addr` with the DMA engine
+    /// The result is the ones' complement of the ones' complement sum of the bytes taken as
+    /// big-endian words, ready to be written big-endian into a header. Passing a previous
+    /// result as `seed` continues that checksum, if the previous range had an even length.
+    fn dma_checksum(&mut self, addr: u16, len: u16, seed: Option<u16>) -> Result<u16, Self::Error> {
+        self.check_sram_range(addr, len)?;
+        self.wait_dma_done()?;
+        let econ1_mode = match seed {
+            Some(seed) => {
+                // Set DMACSSD (ECON1<3>) to seed the checksum from EDMACS
+                self.spi_port.write_reg_16b(spi::addrs::EDMACS, seed)?;
+                0x08
+            },
+            None => 0,
+        };
+        // Clear DMACPY (ECON1<4>) and DMANOCS (ECON1<2>): checksum without copying
+        nb::block!(self.start_dma(addr, 0, len, econ1_mode))?;
+        self.wait_dma_done()?;
+        self.spi_port.read_reg_16b(spi::addrs::EDMACS)
+    }
+
+    /// Verify the IPv4, TCP, UDP, ICMP and ICMPv6 checksums of received frames with the
+    /// DMA engine while they are in the RX ring; `receive_next` drops frames that fail,
+    /// counting them in `RxErrorCounters::checksum_error`.
+    /// IP frames whose checksums cannot all be located, such as IPv4 fragments and IPv6
+    /// frames with extension headers, are dropped too and counted in `checksum_unverified`,
+    /// so that every IP frame delivered has had its checksums verified.
+    fn set_rx_checksum_offload(&mut self, enable: bool) {
+        self.rx_checksum_offload = enable;
+    }
+    fn is_rx_checksum_offload(&self) -> bool {
+        self.rx_checksum_offload
+    }
+
     /// Set controller to Promiscuous Mode
-    fn set_promiscuous(&mut self) -> Result<(), EthControllerError> {
+    fn set_promiscuous(&mut self) -> Result<(), Self::Error> {
         // From Section 10.12, ENC424J600 Data Sheet:
         // "To accept all incoming frames regardless of content (Promiscuous mode),
         // set the CRCEN, RUNTEN, UCEN, NOTMEEN and MCEN bits."
@@ -193,14 +898,1184 @@ impl <'c, SPI: Transfer<u8>,
         Ok(())
     }
 
-    /// Read MAC to [u8; 6]
-    fn read_from_mac(&mut self, mac: &mut [u8]) -> Result<(), EthControllerError> {
+    /// Program every ERXFCON filter bit at once
+    /// e.g. `set_rx_filter(RxFilter::default())` turns Promiscuous Mode off again
+    fn set_rx_filter(&mut self, filter: rx::RxFilter) -> Result<(), Self::Error> {
+        self.spi_port.write_reg_16b(spi::addrs::ERXFCON, filter.bits())?;
+        Ok(())
+    }
+
+    /// Accept frames to a multicast group through the hash table filter
+    /// Sets HTEN (ERXFCON<15>). Groups sharing a hash bit are reference-counted,
+    /// so each `add_multicast` needs a matching `remove_multicast`.
+    fn add_multicast(&mut self, addr: MacAddress) -> Result<(), Self::Error> {
+        if !addr.is_multicast() {
+            return Err(EthControllerError::InvalidMacAddress(addr))
+        }
+        if let Some(bit) = self.hash_table.add(&addr) {
+            self.write_hash_table_word(bit)?;
+        }
+        self.spi_port.set_bits_8b(spi::addrs::ERXFCON + 1, (rx::erxfcon::HTEN >> 8) as u8)?;
+        Ok(())
+    }
+
+    /// Drop a multicast group added with `add_multicast`
+    fn remove_multicast(&mut self, addr: MacAddress) -> Result<(), Self::Error> {
+        if let Some(bit) = self.hash_table.remove(&addr) {
+            self.write_hash_table_word(bit)?;
+        }
+        Ok(())
+    }
+
+    /// Drop all multicast groups and clear EHT1-EHT4
+    fn clear_multicast(&mut self) -> Result<(), Self::Error> {
+        self.hash_table.clear();
+        for addr in [spi::addrs::EHT1, spi::addrs::EHT2, spi::addrs::EHT3, spi::addrs::EHT4].iter() {
+            self.spi_port.write_reg_16b(*addr, 0x0000)?;
+        }
+        Ok(())
+    }
+
+    /// Program the pattern match filter
+    /// Byte `i` of `pattern` is compared at `offset + i` from the start of the frame
+    /// if bit `i` of `mask` is set; the checksum of the selected bytes is written to
+    /// EPMCS. `mode` and `inverted` are written to PMEN<3:0> and NOTPM in ERXFCON.
+    fn set_pattern_match(&mut self, offset: u16, pattern: &[u8], mask: u64,
+                         mode: rx::PatternMatchMode, inverted: bool) -> Result<(), Self::Error> {
+        if pattern.len() > rx::PATTERN_WINDOW_LENGTH ||
+            (pattern.len() < rx::PATTERN_WINDOW_LENGTH && mask >> pattern.len() != 0) {
+            return Err(EthControllerError::InvalidPattern)
+        }
+        // Set EPMM1-EPMM4 to the 64-bit mask
+        self.spi_port.write_reg_16b(spi::addrs::EPMM1, mask as u16)?;
+        self.spi_port.write_reg_16b(spi::addrs::EPMM2, (mask >> 16) as u16)?;
+        self.spi_port.write_reg_16b(spi::addrs::EPMM3, (mask >> 32) as u16)?;
+        self.spi_port.write_reg_16b(spi::addrs::EPMM4, (mask >> 48) as u16)?;
+        // Set EPMCS to the expected checksum
+        self.spi_port.write_reg_16b(spi::addrs::EPMCS, rx::pattern_match_checksum(pattern, mask))?;
+        // Set EPMO to the window offset
+        self.spi_port.write_reg_16b(spi::addrs::EPMO, offset)?;
+        // Set PMEN<3:0> and NOTPM in ERXFCON
+        let filter = self.read_rx_filter()?
+            .pattern_match(mode)
+            .pattern_match_inverted(inverted);
+        self.set_rx_filter(filter)
+    }
+
+    /// Accept only Magic Packets for the station address and assert INTn on reception
+    /// Sets MPEN as the only filter in ERXFCON, and PKTIE and INTIE in EIE, so that
+    /// the MCU can sleep until INTn wakes it. Returns the previous filter, to be
+    /// restored with `set_rx_filter` once awake.
+    fn enable_wake_on_lan(&mut self) -> Result<rx::RxFilter, Self::Error> {
+        let previous = self.read_rx_filter()?;
+        let filter = rx::RxFilter::new()
+            .magic_packet(true)
+            .crc_policy(rx::ErrorFramePolicy::Reject);
+        self.set_rx_filter(filter)?;
+        self.spi_port.set_bits_8b(spi::addrs::EIE, interrupt::Interrupts::PKT.bits() as u8)?;
+        self.spi_port.set_bits_8b(spi::addrs::EIE + 1, (interrupt::INTIE >> 8) as u8)?;
+        Ok(previous)
+    }
+
+    /// Read back the current ERXFCON filter configuration
+    fn read_rx_filter(&mut self) -> Result<rx::RxFilter, Self::Error> {
+        let erxfcon = self.spi_port.read_reg_16b(spi::addrs::ERXFCON)?;
+        Ok(rx::RxFilter::from_bits(erxfcon))
+    }
+
+    /// Read the station MAC address
+    fn read_mac_address(&mut self) -> Result<MacAddress, Self::Error> {
+        let mut mac = [0; 6];
         mac[0] = self.spi_port.read_reg_8b(spi::addrs::MAADR1)?;
         mac[1] = self.spi_port.read_reg_8b(spi::addrs::MAADR1 + 1)?;
         mac[2] = self.spi_port.read_reg_8b(spi::addrs::MAADR2)?;
         mac[3] = self.spi_port.read_reg_8b(spi::addrs::MAADR2 + 1)?;
         mac[4] = self.spi_port.read_reg_8b(spi::addrs::MAADR3)?;
         mac[5] = self.spi_port.read_reg_8b(spi::addrs::MAADR3 + 1)?;
+        Ok(MacAddress(mac))
+    }
+
+    /// Program the station MAC address, overriding the factory address
+    /// Multicast (and broadcast) addresses are rejected.
+    /// A reset through `init_dev` restores the factory address.
+    fn set_mac_address(&mut self, mac: MacAddress) -> Result<(), Self::Error> {
+        if mac.is_multicast() {
+            return Err(EthControllerError::InvalidMacAddress(mac))
+        }
+        let mac = mac.as_bytes();
+        self.spi_port.write_reg_8b(spi::addrs::MAADR1, mac[0])?;
+        self.spi_port.write_reg_8b(spi::addrs::MAADR1 + 1, mac[1])?;
+        self.spi_port.write_reg_8b(spi::addrs::MAADR2, mac[2])?;
+        self.spi_port.write_reg_8b(spi::addrs::MAADR2 + 1, mac[3])?;
+        self.spi_port.write_reg_8b(spi::addrs::MAADR3, mac[4])?;
+        self.spi_port.write_reg_8b(spi::addrs::MAADR3 + 1, mac[5])?;
         Ok(())
     }
+
+    /// Read a PHY register through MII management
+    fn read_phy_reg(&mut self, addr: u8) -> Result<u16, Self::Error> {
+        // Set MIREGADR to the PHY register address
+        self.spi_port.write_reg_16b(spi::addrs::MIREGADR, phy::MIREGADR_RESERVED | addr as u16)?;
+        // Set MIIRD (MICMD<0>) to start the read
+        let micmd = self.spi_port.read_reg_8b(spi::addrs::MICMD)?;
+        self.spi_port.write_reg_8b(spi::addrs::MICMD, 0x01 | (micmd & 0xfe))?;
+        // Poll BUSY (MISTAT<0>) to check if it is reset
+        self.wait_phy_ready()?;
+        // Clear MIIRD (MICMD<0>)
+        self.spi_port.write_reg_8b(spi::addrs::MICMD, micmd & 0xfe)?;
+        // Read the result from MIRD
+        let mird = self.spi_port.read_reg_16b(spi::addrs::MIRD)?;
+        Ok(mird)
+    }
+
+    /// Get the current link status
+    /// Link and duplex are read from PHYLNK (ESTAT<8>) and PHYDPX (ESTAT<10>);
+    /// the speed is read from SPDDPX (PHSTAT3<4:2>). The link is reported down
+    /// until the PHY reports a valid speed.
+    fn link_status(&mut self) -> Result<phy::LinkStatus, Self::Error> {
+        let estat = self.spi_port.read_reg_16b(spi::addrs::ESTAT)?;
+        if estat & 0x0100 == 0 {
+            return Ok(phy::LinkStatus::Down)
+        }
+        let duplex = if estat & 0x0400 == 0x0400 { phy::Duplex::Full } else { phy::Duplex::Half };
+        match self.read_phstat3()?.speed_duplex() {
+            Some((speed, _)) => Ok(phy::LinkStatus::Up { speed, duplex }),
+            None => Ok(phy::LinkStatus::Down)
+        }
+    }
+
+    /// Check and clear LINKIF (EIR<11>)
+    /// Returns true if the link went up or down since the last call
+    fn link_changed(&mut self) -> Result<bool, Self::Error> {
+        let eir_hi = self.spi_port.read_reg_8b(spi::addrs::EIR + 1)?;
+        if eir_hi & 0x08 == 0 {
+            return Ok(false)
+        }
+        self.spi_port.clear_bits_8b(spi::addrs::EIR + 1, 0x08)?;
+        Ok(true)
+    }
+
+    /// Configure auto-negotiation or forced speed/duplex on the PHY
+    /// The configuration is kept and re-applied by `init_dev`.
+    /// Call `sync_mac_to_link` once the link is up to match the MAC duplex settings.
+    fn set_link_config(&mut self, config: phy::LinkConfig) -> Result<(), Self::Error> {
+        self.link_config = config;
+        match config {
+            phy::LinkConfig::AutoNegotiate(abilities) => {
+                // Advertise the abilities, then restart auto-negotiation
+                self.write_phana(abilities | phy::Phana::ADIEEE)?;
+                self.write_phcon1(phy::Phcon1::ANEN | phy::Phcon1::RENEG)?;
+            },
+            phy::LinkConfig::Forced { speed, duplex } => {
+                let mut phcon1 = phy::Phcon1::empty();
+                if speed == phy::Speed::Mbps100 { phcon1 |= phy::Phcon1::SPD100 }
+                if duplex == phy::Duplex::Full { phcon1 |= phy::Phcon1::PFULDPX }
+                self.write_phcon1(phcon1)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Match the MAC duplex settings to the current link
+    /// Sets FULDPX (MACON2<0>), MABBIPG and MAIPG as required once
+    /// auto-negotiation completes.
+    /// Nothing is changed while the link is down.
+    fn sync_mac_to_link(&mut self) -> Result<phy::LinkStatus, Self::Error> {
+        let link_status = self.link_status()?;
+        if let phy::LinkStatus::Up { duplex, .. } = link_status {
+            let macon2_lo = self.spi_port.read_reg_8b(spi::addrs::MACON2)?;
+            let (fuldpx, mabbipg) = match duplex {
+                phy::Duplex::Full => (0x01, MABBIPG_FULL_DUPLEX),
+                phy::Duplex::Half => (0x00, MABBIPG_HALF_DUPLEX),
+            };
+            self.spi_port.write_reg_8b(spi::addrs::MACON2, fuldpx | (macon2_lo & 0xfe))?;
+            self.spi_port.write_reg_16b(spi::addrs::MABBIPG, mabbipg)?;
+            self.spi_port.write_reg_16b(spi::addrs::MAIPG, MAIPG_DEFAULT)?;
+        }
+        Ok(link_status)
+    }
+
+    /// Enable INTn for the given interrupt sources only
+    /// INTn/SPISEL must then be tri-stated on the MCU side (see README).
+    fn enable_interrupts(&mut self, interrupts: interrupt::Interrupts) -> Result<(), Self::Error> {
+        // Set EIE to the chosen sources, then INTIE (EIE<15>)
+        self.spi_port.write_reg_16b(spi::addrs::EIE, interrupts.bits())?;
+        self.spi_port.set_bits_8b(spi::addrs::EIE + 1, (interrupt::INTIE >> 8) as u8)?;
+        Ok(())
+    }
+
+    /// Disable INTn by clearing INTIE (EIE<15>)
+    fn disable_interrupts(&mut self) -> Result<(), Self::Error> {
+        self.spi_port.clear_bits_8b(spi::addrs::EIE + 1, (interrupt::INTIE >> 8) as u8)?;
+        Ok(())
+    }
+
+    /// Read and clear the interrupt flags in EIR, returning those that were set
+    /// PKTIF cannot be cleared here; it stays set until every packet is
+    /// received with `receive_next`. LINKIF is cleared as well, so a set
+    /// `Interrupts::LINK` replaces a call to `link_changed`.
+    /// INTIE is cleared while handling so that INTn re-asserts on any flag
+    /// that is set in the meantime.
+    fn handle_interrupt(&mut self) -> Result<interrupt::Interrupts, Self::Error> {
+        let eie_hi = self.spi_port.read_reg_8b(spi::addrs::EIE + 1)?;
+        let intie = (interrupt::INTIE >> 8) as u8;
+        self.spi_port.clear_bits_8b(spi::addrs::EIE + 1, intie)?;
+        let eir = self.spi_port.read_reg_16b(spi::addrs::EIR)?;
+        let fired = interrupt::Interrupts::from_bits_truncate(eir);
+        // Clear only the flags that were read as set
+        let clear = fired - interrupt::Interrupts::PKT;
+        self.spi_port.clear_bits_8b(spi::addrs::EIR, (clear.bits() & 0xff) as u8)?;
+        self.spi_port.clear_bits_8b(spi::addrs::EIR + 1, (clear.bits() >> 8) as u8)?;
+        if eie_hi & intie == intie {
+            self.spi_port.set_bits_8b(spi::addrs::EIE + 1, intie)?;
+        }
+        Ok(fired)
+    }
+
+    /// Write a PHY register through MII management
+    fn write_phy_reg(&mut self, addr: u8, data: u16) -> Result<(), Self::Error> {
+        // Set MIREGADR to the PHY register address
+        self.spi_port.write_reg_16b(spi::addrs::MIREGADR, phy::MIREGADR_RESERVED | addr as u16)?;
+        // Write MIWR; writing the high byte starts the write
+        self.spi_port.write_reg_16b(spi::addrs::MIWR, data)?;
+        // Poll BUSY (MISTAT<0>) to check if it is reset
+        self.wait_phy_ready()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate std;
+    use std::string::ToString;
+    use sim::{Enc424j600, SimDelay, SimNss, SimSpi};
+
+    const FRAME: [u8; 60] = {
+        let mut frame = [0x5a; 60];
+        let mut i = 0;
+        while i < 6 {
+            frame[i] = 0xff;
+            i += 1;
+        }
+        frame
+    };
+
+    /// Broadcast IPv4/UDP frame from 192.168.0.1 to 192.168.0.199, checksums left at zero
+    fn udp_frame() -> [u8; 60] {
+        let mut frame = [0; 60];
+        frame[..6].copy_from_slice(&[0xff; 6]);
+        frame[12..14].copy_from_slice(&[0x08, 0x00]);
+        frame[14..34].copy_from_slice(&[0x45, 0x00, 0x00, 0x21, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11,
+                                        0x00, 0x00, 0xc0, 0xa8, 0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7]);
+        frame[34..42].copy_from_slice(&[0x04, 0x00, 0x04, 0x01, 0x00, 0x0d, 0x00, 0x00]);
+        frame[42..47].copy_from_slice(b"hello");
+        frame
+    }
+
+    fn setup() -> (Enc424j600, SpiEth<SimSpi, SimNss>) {
+        let chip = Enc424j600::new();
+        let mut eth = SpiEth::new(chip.spi(), chip.nss()).unwrap();
+        assert!(eth.init_dev(&mut SimDelay).is_ok());
+        assert!(eth.init_rxbuf().is_ok());
+        assert!(eth.init_txbuf().is_ok());
+        (chip, eth)
+    }
+
+    /// Receive frames of varying length through the RX ring for `rounds` frames,
+    /// returning the number of frames that straddled the end of SRAM
+    fn cycle_rx_ring(chip: &Enc424j600, eth: &mut SpiEth<SimSpi, SimNss>, rounds: usize) -> usize {
+        let mut straddled = 0;
+        for i in 0..rounds {
+            let len = 60 + (i * 7) % 41;
+            let mut frame = [0; 100];
+            frame[..6].copy_from_slice(&[0xff; 6]);
+            for (j, byte) in frame[6..len].iter_mut().enumerate() {
+                *byte = (i + j) as u8;
+            }
+            let head = chip.read_reg(spi::addrs::ERXHEAD) as usize;
+            // Next packet pointer, RSV, frame and FCS
+            if head + 2 + rx::RSV_LENGTH + len + 4 > rx::RX_MAX_ADDRESS as usize + 1 {
+                straddled += 1;
+            }
+            assert!(chip.receive(&frame[..len]));
+            let packet = eth.receive_next(false).unwrap();
+            assert_eq!(packet.get_payload(), &frame[..len]);
+            let tail = chip.read_reg(spi::addrs::ERXTAIL);
+            assert!(tail >= chip.read_reg(spi::addrs::ERXST) && tail < rx::RX_MAX_ADDRESS);
+        }
+        straddled
+    }
+
+    #[test]
+    fn receive_next_across_ring_wrap() {
+        let (chip, mut eth) = setup();
+        assert!(cycle_rx_ring(&chip, &mut eth, 200) >= 4);
+    }
+
+    #[test]
+    fn receive_next_with_configured_rx_start() {
+        let chip = Enc424j600::new();
+        let layout = MemoryLayout { tx_size: 0x5a00, user_size: 0 };
+        let mut eth = SpiEth::with_memory_layout(chip.spi(), chip.nss(), layout).unwrap();
+        assert!(eth.init_dev(&mut SimDelay).is_ok());
+        assert!(eth.init_rxbuf().is_ok());
+        assert_eq!(chip.read_reg(spi::addrs::ERXST), 0x5a00);
+        assert_eq!(chip.read_reg(spi::addrs::ERXHEAD), 0x5a00);
+        assert!(cycle_rx_ring(&chip, &mut eth, 200) >= 8);
+    }
+
+    #[test]
+    fn memory_layout() {
+        let chip = Enc424j600::new();
+        for &(tx_size, user_size) in [(0x5341, 0), (0x4000, 0x0101), (0x0400, 0), (0x5000, 0x0c00),
+                                      (0xffff, 0xffff)].iter() {
+            let layout = MemoryLayout { tx_size, user_size };
+            assert!(matches!(SpiEth::with_memory_layout(chip.spi(), chip.nss(), layout),
+                Err(EthControllerError::InvalidMemoryLayout(l)) if l == layout));
+        }
+        let layout = MemoryLayout { tx_size: 0x0800, user_size: 0x1000 };
+        assert_eq!(layout.get_user_region(), Some((0x0800, 0x17ff)));
+        assert_eq!(layout.get_rx_start(), 0x1800);
+        assert_eq!(layout.get_rx_size(), 0x4800);
+        let mut eth = SpiEth::with_memory_layout(chip.spi(), chip.nss(), layout).unwrap();
+        assert!(eth.init_dev(&mut SimDelay).is_ok());
+        assert!(eth.init_rxbuf().is_ok());
+        assert!(eth.init_txbuf().is_ok());
+        assert_eq!(eth.get_memory_layout(), layout);
+        assert_eq!(chip.read_reg(spi::addrs::ERXST), 0x1800);
+        assert_eq!(chip.read_reg(spi::addrs::ERXTAIL), rx::RX_MAX_ADDRESS - 1);
+        assert_eq!(chip.read_reg(spi::addrs::EUDAST), 0x0800);
+        assert_eq!(chip.read_reg(spi::addrs::EUDAND), 0x17ff);
+        assert!(chip.receive(&FRAME));
+        assert_eq!(eth.receive_next(false).unwrap().get_payload(), &FRAME[..]);
+        assert_eq!(chip.read_sram(0x1800 + 8, FRAME.len()), &FRAME[..]);
+        // No user data region in the default layout
+        let (chip, _eth) = setup();
+        assert_eq!(chip.read_reg(spi::addrs::EUDAST), 0x6000);
+    }
+
+    #[test]
+    fn init_dev_resets_chip() {
+        let chip = Enc424j600::new();
+        chip.write_reg(spi::addrs::ERXFCON, 0xffff);
+        let mut eth = SpiEth::new(chip.spi(), chip.nss()).unwrap();
+        assert!(eth.init_dev(&mut SimDelay).is_ok());
+        assert_eq!(chip.read_reg(spi::addrs::EUDAST), 0x0000);
+        assert_eq!(chip.read_reg(spi::addrs::ERXFCON), 0x0059);
+    }
+
+    #[test]
+    fn init_dev_reports_spi_error() {
+        let chip = Enc424j600::new();
+        let mut eth = SpiEth::new(chip.spi(), chip.nss()).unwrap();
+        chip.set_spi_fault(true);
+        match eth.init_dev(&mut SimDelay) {
+            Err(EthControllerError::Spi { opcode, addr, error }) => {
+                assert_eq!(opcode, spi::opcodes::WCRU);
+                assert_eq!(addr, Some(spi::addrs::EUDAST));
+                assert_eq!(error, sim::SimError);
+            },
+            _ => panic!("expected SPI error"),
+        }
+    }
+
+    #[test]
+    fn set_mac_address() {
+        let (chip, mut eth) = setup();
+        let mac = MacAddress::new([0x02, 0x00, 0x5e, 0x10, 0x20, 0x30]);
+        assert!(eth.set_mac_address(mac).is_ok());
+        assert_eq!(eth.read_mac_address().unwrap(), mac);
+        assert_eq!(chip.read_reg(spi::addrs::MAADR1), 0x0002);
+        assert_eq!(chip.read_reg(spi::addrs::MAADR3), 0x3020);
+        let mut frame = FRAME;
+        frame[..6].copy_from_slice(mac.as_bytes());
+        assert!(chip.receive(&frame));
+        let multicast = MacAddress::new([0x01, 0x00, 0x5e, 0x00, 0x00, 0xfb]);
+        assert!(matches!(eth.set_mac_address(multicast),
+            Err(EthControllerError::InvalidMacAddress(_))));
+        assert!(matches!(eth.set_mac_address(MacAddress::BROADCAST),
+            Err(EthControllerError::InvalidMacAddress(_))));
+        assert_eq!(eth.read_mac_address().unwrap(), mac);
+        assert_eq!(mac.to_string(), "02-00-5e-10-20-30");
+    }
+
+    #[test]
+    fn error_display() {
+        let error: SpiEthError<SimSpi, SimNss> =
+            EthControllerError::ResetVerification { expected: 0x1234, found: 0x0000 };
+        assert_eq!(error.to_string(),
+            "Reset verification failed: EUDAST = 0x0000, expected 0x1234");
+    }
+
+    #[test]
+    fn init_dev_times_out_on_clock_ready() {
+        let chip = Enc424j600::new();
+        chip.set_clock_ready(false);
+        let mut eth = SpiEth::new(chip.spi(), chip.nss()).unwrap();
+        eth.set_poll_limits(PollLimits { clock_ready_us: 1_000, ..PollLimits::default() });
+        assert!(matches!(eth.init_dev(&mut SimDelay), Err(EthControllerError::ClockReadyTimeout)));
+    }
+
+    #[test]
+    fn read_factory_mac() {
+        let (_chip, mut eth) = setup();
+        assert_eq!(eth.read_mac_address().unwrap(), MacAddress::new(sim::FACTORY_MAC));
+    }
+
+    #[test]
+    fn phy_register_access() {
+        let (chip, mut eth) = setup();
+        let phstat1 = eth.read_phstat1().unwrap();
+        assert!(phstat1.contains(phy::Phstat1::LLSTAT | phy::Phstat1::ANDONE));
+        assert_eq!(eth.read_phanlpa().unwrap(), sim::PARTNER_ABILITIES);
+        assert_eq!(eth.read_phstat3().unwrap().speed_duplex(),
+            Some((phy::Speed::Mbps100, phy::Duplex::Full)));
+        // Force 10 Mbps, half duplex
+        assert!(eth.write_phcon1(phy::Phcon1::empty()).is_ok());
+        assert_eq!(chip.read_phy_reg(phy::addrs::PHCON1), 0x0000);
+        assert_eq!(eth.read_phstat3().unwrap().speed_duplex(),
+            Some((phy::Speed::Mbps10, phy::Duplex::Half)));
+        assert!(eth.write_phy_reg(phy::addrs::PHANA, 0x0021).is_ok());
+        assert_eq!(eth.read_phana().unwrap(), phy::Phana::AD10 | phy::Phana::ADIEEE);
+    }
+
+    #[test]
+    fn link_status_and_changes() {
+        let (chip, mut eth) = setup();
+        assert_eq!(eth.link_status().unwrap(),
+            phy::LinkStatus::Up { speed: phy::Speed::Mbps100, duplex: phy::Duplex::Full });
+        assert!(!eth.link_changed().unwrap());
+        chip.set_link_up(false);
+        assert!(eth.link_changed().unwrap());
+        assert!(!eth.link_changed().unwrap());
+        assert_eq!(eth.link_status().unwrap(), phy::LinkStatus::Down);
+        chip.set_partner_abilities(phy::Phanlpa::LP10 | phy::Phanlpa::LPIEEE);
+        chip.set_link_up(true);
+        assert!(eth.link_changed().unwrap());
+        assert_eq!(eth.link_status().unwrap(),
+            phy::LinkStatus::Up { speed: phy::Speed::Mbps10, duplex: phy::Duplex::Half });
+    }
+
+    #[test]
+    fn init_dev_applies_forced_link() {
+        let chip = Enc424j600::new();
+        let mut eth = SpiEth::new(chip.spi(), chip.nss()).unwrap();
+        assert!(eth.set_link_config(phy::LinkConfig::Forced {
+            speed: phy::Speed::Mbps10,
+            duplex: phy::Duplex::Half
+        }).is_ok());
+        assert!(eth.init_dev(&mut SimDelay).is_ok());
+        assert_eq!(chip.read_phy_reg(phy::addrs::PHCON1), 0x0000);
+        let link_status = eth.sync_mac_to_link().unwrap();
+        assert_eq!(link_status,
+            phy::LinkStatus::Up { speed: phy::Speed::Mbps10, duplex: phy::Duplex::Half });
+        assert_eq!(chip.read_reg(spi::addrs::MACON2) & 0x0001, 0);
+        assert_eq!(chip.read_reg(spi::addrs::MABBIPG), 0x12);
+    }
+
+    #[test]
+    fn sync_mac_to_negotiated_link() {
+        let (chip, mut eth) = setup();
+        assert!(eth.set_link_config(phy::LinkConfig::AutoNegotiate(
+            phy::Phana::AD100 | phy::Phana::AD10FD)).is_ok());
+        assert_eq!(chip.read_phy_reg(phy::addrs::PHANA), 0x00c1);
+        assert_eq!(eth.sync_mac_to_link().unwrap(),
+            phy::LinkStatus::Up { speed: phy::Speed::Mbps100, duplex: phy::Duplex::Half });
+        assert!(eth.set_link_config(phy::LinkConfig::default()).is_ok());
+        // The default advertises the reset abilities, symmetric PAUSE included
+        assert_eq!(chip.read_phy_reg(phy::addrs::PHANA), 0x05e1);
+        assert_eq!(eth.sync_mac_to_link().unwrap(),
+            phy::LinkStatus::Up { speed: phy::Speed::Mbps100, duplex: phy::Duplex::Full });
+        assert_eq!(chip.read_reg(spi::addrs::MACON2) & 0x0001, 1);
+        assert_eq!(chip.read_reg(spi::addrs::MABBIPG), 0x15);
+    }
+
+    #[test]
+    fn interrupt_dispatch() {
+        use interrupt::Interrupts;
+        let (chip, mut eth) = setup();
+        assert!(eth.enable_interrupts(Interrupts::PKT | Interrupts::LINK).is_ok());
+        assert_eq!(chip.read_reg(spi::addrs::EIE), 0x8840);
+        assert!(!chip.interrupt_asserted());
+        chip.set_link_up(false);
+        assert!(chip.interrupt_asserted());
+        assert_eq!(eth.handle_interrupt().unwrap(), Interrupts::LINK);
+        assert!(!chip.interrupt_asserted());
+        // PKTIF stays set until the packet is read
+        assert!(chip.receive(&FRAME));
+        assert_eq!(eth.handle_interrupt().unwrap(), Interrupts::PKT);
+        assert!(chip.interrupt_asserted());
+        assert!(eth.receive_next(false).is_ok());
+        assert!(!chip.interrupt_asserted());
+        // TXIF fires without asserting INTn as it is not enabled
+        let mut packet = tx::TxPacket::new();
+        packet.update_frame(&FRAME, FRAME.len());
+        assert!(eth.send_raw_packet(&packet).is_ok());
+        assert!(!chip.interrupt_asserted());
+        assert_eq!(eth.handle_interrupt().unwrap(), Interrupts::TX);
+        assert!(eth.disable_interrupts().is_ok());
+        assert_eq!(chip.read_reg(spi::addrs::EIE), 0x0840);
+    }
+
+    #[test]
+    fn send_raw_packet_waits_for_room() {
+        let (chip, mut eth) = setup();
+        let mut packet = tx::TxPacket::new();
+        packet.update_frame(&FRAME, FRAME.len());
+        chip.set_tx_latency(5);
+        let mut queued = 0;
+        while eth.queue_packet(&packet).is_ok() {
+            queued += 1;
+        }
+        assert!(matches!(eth.queue_packet(&packet), Err(EthControllerError::TxQueueFull)));
+        // The blocking send waits for room instead of failing, then for its own frame
+        assert!(eth.send_raw_packet(&packet).is_ok());
+        assert_eq!(chip.take_transmitted().len(), queued + 1);
+        assert!(matches!(eth.service_tx_queue(), Ok(None)));
+    }
+
+    #[test]
+    fn send_raw_packet_after_aborted_frame() {
+        let (chip, mut eth) = setup();
+        let mut first = tx::TxPacket::new();
+        first.update_frame(&FRAME[..42], 42);
+        let mut packet = tx::TxPacket::new();
+        packet.update_frame(&FRAME, FRAME.len());
+        chip.set_tx_status(tx::etxstat::MAXCOL | 15);
+        assert!(eth.queue_packet(&first).is_ok());
+        // Reading ECON1 lets the simulated MAC abort the first frame
+        assert!(eth.poll_dma().is_ok());
+        chip.set_tx_status(0);
+        let status = eth.send_raw_packet(&packet).unwrap();
+        assert!(!status.is_aborted());
+        assert_eq!(chip.take_transmitted(), [FRAME.to_vec()]);
+        assert!(matches!(eth.service_tx_queue(), Ok(None)));
+    }
+
+    #[test]
+    fn tx_abort_after_interrupt() {
+        use interrupt::Interrupts;
+        let (chip, mut eth) = setup();
+        assert!(eth.enable_interrupts(Interrupts::TX | Interrupts::TXABT).is_ok());
+        let mut packet = tx::TxPacket::new();
+        packet.update_frame(&FRAME, FRAME.len());
+        chip.set_tx_status(tx::etxstat::LATECOL);
+        assert!(eth.queue_packet(&packet).is_ok());
+        // The interrupt handler clears TXABTIF before the queue is serviced
+        assert!(chip.interrupt_asserted());
+        assert_eq!(eth.handle_interrupt().unwrap(), Interrupts::TXABT);
+        assert!(matches!(eth.service_tx_queue(),
+            Err(EthControllerError::TxAborted(status)) if status.is_late_collision()));
+        assert!(matches!(eth.service_tx_queue(), Ok(None)));
+    }
+
+    #[test]
+    fn receive_next_after_oversized_frame() {
+        let chip = Enc424j600::new();
+        // RX ring large enough to hold a frame over RAW_FRAME_LENGTH_MAX
+        let layout = MemoryLayout { tx_size: 0x0800, user_size: 0 };
+        let mut eth = SpiEth::with_memory_layout(chip.spi(), chip.nss(), layout).unwrap();
+        assert!(eth.init_dev(&mut SimDelay).is_ok());
+        assert!(eth.init_rxbuf().is_ok());
+        let mut oversized = [0x5a; RAW_FRAME_LENGTH_MAX];
+        oversized[..6].copy_from_slice(&[0xff; 6]);
+        assert!(chip.receive(&oversized));
+        assert!(chip.receive(&FRAME));
+        assert!(matches!(eth.receive_next(false),
+            Err(EthControllerError::FrameTooLarge { len, .. }) if len == RAW_FRAME_LENGTH_MAX + 4));
+        assert_eq!(eth.receive_next(false).unwrap().get_payload(), &FRAME[..]);
+        assert!(matches!(eth.receive_next(false), Err(EthControllerError::NoRxPacket)));
+        assert_eq!(chip.read_reg(spi::addrs::EIR) & 0x0040, 0);
+    }
+
+    #[test]
+    fn receive_next_without_packet() {
+        let (_chip, mut eth) = setup();
+        assert!(matches!(eth.receive_next(false), Err(EthControllerError::NoRxPacket)));
+    }
+
+    #[test]
+    fn receive_next_packets_in_order() {
+        let (chip, mut eth) = setup();
+        let mut second = FRAME;
+        second[20] = 0xa5;
+        assert!(chip.receive(&FRAME));
+        assert!(chip.receive(&second));
+        for expected in [&FRAME, &second].iter() {
+            let packet = match eth.receive_next(false) {
+                Ok(packet) => packet,
+                Err(_) => panic!("no packet received"),
+            };
+            // Frame length includes the 4-byte FCS
+            assert_eq!(packet.get_frame_length(), FRAME.len() + 4);
+            let mut frame = [0; RAW_FRAME_LENGTH_MAX];
+            packet.write_frame_to(&mut frame);
+            assert_eq!(&frame[..FRAME.len()], &expected[..]);
+            assert_eq!(packet.get_payload(), &expected[..]);
+            assert_eq!(packet.get_fcs(), rx::crc32(&expected[..]));
+        }
+        assert!(eth.receive_next(false).is_err());
+        // PKTIF (EIR<6>) cleared once PKTCNT reaches zero
+        assert_eq!(chip.read_reg(spi::addrs::EIR) & 0x0040, 0);
+    }
+
+    #[test]
+    fn send_raw_packet_transmits_frame() {
+        let (chip, mut eth) = setup();
+        let mut packet = tx::TxPacket::new();
+        packet.update_frame(&FRAME, FRAME.len());
+        assert!(eth.send_raw_packet(&packet).is_ok());
+        assert!(eth.send_raw_packet(&packet).is_ok());
+        let sent = chip.take_transmitted();
+        assert_eq!(sent.len(), 2);
+        assert!(sent.iter().all(|frame| frame[..] == FRAME[..]));
+    }
+
+    #[test]
+    fn rx_filter_round_trip() {
+        use rx::{ErrorFramePolicy, PatternMatchMode, RxFilter};
+        let (chip, mut eth) = setup();
+        assert_eq!(eth.read_rx_filter().unwrap(), RxFilter::default());
+        assert!(eth.set_promiscuous().is_ok());
+        assert_eq!(eth.read_rx_filter().unwrap(), RxFilter::promiscuous().broadcast(true));
+        let filter = RxFilter::new()
+            .broadcast(true)
+            .hash_table(true)
+            .pattern_match(PatternMatchMode::NotBroadcast)
+            .pattern_match_inverted(true)
+            .crc_policy(ErrorFramePolicy::AcceptOnly)
+            .runt_policy(ErrorFramePolicy::Accept);
+        assert!(eth.set_rx_filter(filter).is_ok());
+        assert_eq!(chip.read_reg(spi::addrs::ERXFCON), 0x9781);
+        let read_back = eth.read_rx_filter().unwrap();
+        assert_eq!(read_back, filter);
+        assert!(read_back.is_broadcast() && read_back.is_hash_table());
+        assert!(!read_back.is_unicast() && !read_back.is_magic_packet());
+        assert_eq!(read_back.get_pattern_match(), Some(PatternMatchMode::NotBroadcast));
+        assert_eq!(read_back.get_crc_policy(), ErrorFramePolicy::AcceptOnly);
+        assert_eq!(read_back.get_runt_policy(), ErrorFramePolicy::Accept);
+    }
+
+    #[test]
+    fn rx_filter_turns_promiscuous_off() {
+        let (chip, mut eth) = setup();
+        let mut frame = FRAME;
+        frame[..6].copy_from_slice(&[0x02, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        assert!(eth.set_promiscuous().is_ok());
+        assert!(chip.receive(&frame));
+        assert!(eth.set_rx_filter(rx::RxFilter::default()).is_ok());
+        assert!(!chip.receive(&frame));
+        assert!(chip.receive(&FRAME));
+        assert!(eth.set_rx_filter(rx::RxFilter::new()).is_ok());
+        assert!(!chip.receive(&FRAME));
+    }
+
+    #[test]
+    fn hash_table_bit_vectors() {
+        // Bits 28:23 of the MSB-first CRC-32, as computed by Microchip's reference driver
+        assert_eq!(rx::hash_table_bit(&MacAddress::new([0x01, 0x00, 0x5e, 0x00, 0x00, 0xfb])), 62);
+        assert_eq!(rx::hash_table_bit(&MacAddress::new([0x33, 0x33, 0xff, 0x12, 0x34, 0x56])), 23);
+        assert_eq!(rx::hash_table_bit(&MacAddress::new([0x01, 0x00, 0x5e, 0x00, 0x00, 0x01])), 63);
+    }
+
+    #[test]
+    fn multicast_hash_table() {
+        let (chip, mut eth) = setup();
+        let mdns = MacAddress::new([0x01, 0x00, 0x5e, 0x00, 0x00, 0xfb]);
+        let mut frame = FRAME;
+        frame[..6].copy_from_slice(mdns.as_bytes());
+        assert!(!chip.receive(&frame));
+        assert!(eth.add_multicast(mdns).is_ok());
+        assert!(eth.read_rx_filter().unwrap().is_hash_table());
+        assert!(chip.receive(&frame));
+        // A group hashing to the same bit keeps it set until both are removed
+        let bit = rx::hash_table_bit(&mdns);
+        let same_bit = (0..=0xff_u8)
+            .map(|b| MacAddress::new([0x33, 0x33, 0xff, 0x00, 0x00, b]))
+            .find(|addr| rx::hash_table_bit(addr) == bit)
+            .unwrap();
+        assert!(eth.add_multicast(same_bit).is_ok());
+        assert!(eth.remove_multicast(mdns).is_ok());
+        assert!(chip.receive(&frame));
+        assert!(eth.remove_multicast(same_bit).is_ok());
+        assert!(!chip.receive(&frame));
+        let eht = [spi::addrs::EHT1, spi::addrs::EHT2, spi::addrs::EHT3, spi::addrs::EHT4];
+        assert!(eth.add_multicast(mdns).is_ok());
+        assert_eq!(eht.iter().map(|addr| chip.read_reg(*addr).count_ones()).sum::<u32>(), 1);
+        assert!(eth.clear_multicast().is_ok());
+        assert!(eht.iter().all(|addr| chip.read_reg(*addr) == 0));
+        assert!(matches!(eth.add_multicast(MacAddress::new(sim::FACTORY_MAC)),
+            Err(EthControllerError::InvalidMacAddress(_))));
+    }
+
+    #[test]
+    fn pattern_match_checksum() {
+        // Selected bytes 0x45, 0x00 | 0x11: 0x4500 + 0x1100 = 0x5600
+        let pattern = [0x45, 0xff, 0x00, 0x11];
+        assert_eq!(rx::pattern_match_checksum(&pattern, 0b1101), !0x5600);
+        // Carries are folded back in
+        assert_eq!(rx::pattern_match_checksum(&[0xff, 0xff, 0x00, 0x02], 0b1111), !0x0002);
+        assert_eq!(rx::pattern_match_checksum(&pattern, 0), 0xffff);
+    }
+
+    #[test]
+    fn pattern_match_filter() {
+        use rx::PatternMatchMode;
+        let (chip, mut eth) = setup();
+        // Accept IPv4 UDP frames: EtherType at 12-13, protocol at 23
+        let pattern = [0x08, 0x00, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x11];
+        let mask = 0b1000_0000_0011;
+        assert!(eth.set_rx_filter(rx::RxFilter::new()).is_ok());
+        assert!(eth.set_pattern_match(12, &pattern, mask, PatternMatchMode::Checksum, false).is_ok());
+        assert_eq!(chip.read_reg(spi::addrs::EPMO), 12);
+        assert_eq!(chip.read_reg(spi::addrs::EPMM1), mask as u16);
+        assert_eq!(eth.read_rx_filter().unwrap().get_pattern_match(), Some(PatternMatchMode::Checksum));
+        let mut udp = FRAME;
+        udp[12..14].copy_from_slice(&[0x08, 0x00]);
+        udp[23] = 0x11;
+        let mut tcp = udp;
+        tcp[23] = 0x06;
+        assert!(chip.receive(&udp));
+        assert!(!chip.receive(&tcp));
+        // NOTPM accepts frames that do not match instead
+        assert!(eth.set_pattern_match(12, &pattern, mask, PatternMatchMode::Checksum, true).is_ok());
+        assert!(!chip.receive(&udp));
+        assert!(chip.receive(&tcp));
+        // Matching frames must not be broadcast
+        assert!(eth.set_pattern_match(12, &pattern, mask, PatternMatchMode::NotBroadcast, false).is_ok());
+        assert!(!chip.receive(&udp));
+        assert!(matches!(eth.set_pattern_match(0, &pattern, 1 << 12, PatternMatchMode::Checksum, false),
+            Err(EthControllerError::InvalidPattern)));
+        assert!(matches!(eth.set_pattern_match(0, &[0; 65], 1, PatternMatchMode::Checksum, false),
+            Err(EthControllerError::InvalidPattern)));
+    }
+
+    #[test]
+    fn wake_on_lan() {
+        let (chip, mut eth) = setup();
+        let station = MacAddress::new(sim::FACTORY_MAC);
+        let other = MacAddress::new([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        let previous = eth.enable_wake_on_lan().unwrap();
+        assert_eq!(previous, rx::RxFilter::default());
+        assert!(!chip.receive(&FRAME));
+        let mut frame = [0; RAW_FRAME_LENGTH_MAX];
+        tx::TxPacket::magic_packet(other, station).write_frame_to(&mut frame);
+        assert!(!chip.receive(&frame[..116]));
+        let packet = tx::TxPacket::magic_packet(station, other);
+        assert_eq!(packet.get_frame_length(), 116);
+        packet.write_frame_to(&mut frame);
+        assert_eq!(&frame[..6], MacAddress::BROADCAST.as_bytes());
+        assert_eq!(&frame[12..20], &[0x08, 0x42, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(&frame[110..116], station.as_bytes());
+        assert!(chip.receive(&frame[..116]));
+        assert!(chip.interrupt_asserted());
+        assert!(eth.set_rx_filter(previous).is_ok());
+        assert!(chip.receive(&FRAME));
+    }
+
+    #[test]
+    fn receive_status_vector() {
+        let (chip, mut eth) = setup();
+        let rsv_bits = |packet: &rx::RxPacket| {
+            let mut bytes = [0; 8];
+            bytes[..rx::RSV_LENGTH].copy_from_slice(packet.read_raw_rsv());
+            u64::from_le_bytes(bytes)
+        };
+        let station = MacAddress::new(sim::FACTORY_MAC);
+        assert!(chip.receive(&FRAME));
+        let packet = eth.receive_next(false).unwrap();
+        assert!(packet.is_received_ok() && packet.is_broadcast() && packet.is_broadcast_match());
+        assert!(!packet.is_crc_error() && !packet.is_multicast() && !packet.is_unicast_match());
+        // Filter match bits 31-38 of Table 9-1
+        assert_eq!((rsv_bits(&packet) >> 31) & 0xff, 0b0100_0000);
+        let mut frame = FRAME;
+        frame[..6].copy_from_slice(station.as_bytes());
+        frame[12..16].copy_from_slice(&[0x81, 0x00, 0x00, 0x01]);
+        assert!(chip.receive(&frame));
+        let packet = eth.receive_next(false).unwrap();
+        assert!(packet.is_unicast_match() && packet.is_vlan() && !packet.is_broadcast());
+        frame[12..16].copy_from_slice(&[0x88, 0x08, 0x00, 0x01]);
+        assert!(chip.receive(&frame));
+        let packet = eth.receive_next(false).unwrap();
+        assert!(packet.is_control_frame() && packet.is_pause_frame() && !packet.is_unknown_opcode());
+        // Length field larger than the data
+        frame[12..14].copy_from_slice(&[0x05, 0xdc]);
+        assert!(chip.receive(&frame));
+        assert!(eth.receive_next(false).unwrap().is_length_check_error());
+        frame[12..14].copy_from_slice(&[0x05, 0xff]);
+        assert!(chip.receive(&frame));
+        assert!(eth.receive_next(false).unwrap().is_length_out_of_range());
+        let filter = rx::RxFilter::promiscuous().crc_policy(rx::ErrorFramePolicy::Accept);
+        eth.set_rx_filter(filter).unwrap();
+        frame[..6].copy_from_slice(&[0x02, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        assert!(chip.receive_with_fcs(&frame, 0));
+        let packet = eth.receive_next(false).unwrap();
+        assert!(packet.is_crc_error() && packet.is_not_me_match() && !packet.is_received_ok());
+        let multicast = MacAddress::new([0x01, 0x00, 0x5e, 0x00, 0x00, 0xfb]);
+        eth.set_rx_filter(rx::RxFilter::new()).unwrap();
+        eth.add_multicast(multicast).unwrap();
+        frame[..6].copy_from_slice(multicast.as_bytes());
+        assert!(chip.receive(&frame));
+        let packet = eth.receive_next(false).unwrap();
+        assert!(packet.is_hash_match() && packet.is_multicast() && !packet.is_multicast_match());
+        assert_eq!((rsv_bits(&packet) >> 31) & 0xff, 0b0000_0100);
+        eth.set_rx_filter(rx::RxFilter::new().multicast(true)).unwrap();
+        assert!(chip.receive(&frame));
+        let packet = eth.receive_next(false).unwrap();
+        assert!(packet.is_multicast_match() && !packet.is_hash_match());
+        eth.enable_wake_on_lan().unwrap();
+        let mut magic = [0; RAW_FRAME_LENGTH_MAX];
+        tx::TxPacket::magic_packet(station, station).write_frame_to(&mut magic);
+        assert!(chip.receive(&magic[..116]));
+        assert!(eth.receive_next(false).unwrap().is_magic_packet_match());
+    }
+
+    #[test]
+    fn rx_error_policy() {
+        use rx::{RxErrorPolicy, RxFrameError};
+        let (chip, mut eth) = setup();
+        let filter = rx::RxFilter::default().crc_policy(rx::ErrorFramePolicy::Accept);
+        eth.set_rx_filter(filter).unwrap();
+        let mut too_long = FRAME;
+        too_long[12..14].copy_from_slice(&[0x05, 0xdc]);
+        // DeliverAll hands over errored frames without flagging them
+        assert!(chip.receive_with_fcs(&FRAME, 0));
+        let packet = eth.receive_next(false).unwrap();
+        assert_eq!(packet.get_rsv_error(), Some(RxFrameError::CrcError));
+        assert_eq!(packet.get_error(), None);
+        eth.set_rx_error_policy(RxErrorPolicy::Flag);
+        assert!(chip.receive(&too_long));
+        assert_eq!(eth.receive_next(false).unwrap().get_error(),
+            Some(RxFrameError::LengthCheckError));
+        assert!(chip.receive(&FRAME));
+        assert_eq!(eth.receive_next(false).unwrap().get_error(), None);
+        eth.set_rx_error_policy(RxErrorPolicy::Drop);
+        assert!(chip.receive_with_fcs(&FRAME, 0));
+        assert!(chip.receive(&too_long));
+        assert!(chip.receive_with_fcs(&FRAME, 0));
+        assert!(chip.receive(&FRAME));
+        assert!(eth.receive_next(false).unwrap().get_rsv_error().is_none());
+        assert!(matches!(eth.receive_next(false), Err(EthControllerError::NoRxPacket)));
+        let counters = eth.get_rx_error_counters();
+        assert_eq!(counters.crc_error, 2);
+        assert_eq!(counters.length_check_error, 1);
+        assert_eq!(counters.total(), 3);
+        eth.reset_rx_error_counters();
+        assert_eq!(eth.get_rx_error_counters(), rx::RxErrorCounters::default());
+    }
+
+    #[test]
+    fn receive_next_poll_times_out() {
+        let (_chip, mut eth) = setup();
+        eth.set_poll_limits(PollLimits { rx_polls: 10, ..PollLimits::default() });
+        assert!(matches!(eth.receive_next(true), Err(EthControllerError::RxTimeout)));
+    }
+
+    #[test]
+    fn send_raw_packet_waits_for_txrts() {
+        let (chip, mut eth) = setup();
+        let mut packet = tx::TxPacket::new();
+        packet.update_frame(&FRAME, FRAME.len());
+        eth.set_poll_limits(PollLimits { tx_polls: 10, ..PollLimits::default() });
+        chip.set_tx_latency(5);
+        assert!(eth.send_raw_packet(&packet).is_ok());
+        chip.set_tx_latency(u32::MAX);
+        assert!(matches!(eth.send_raw_packet(&packet), Err(EthControllerError::TxTimeout)));
+        assert_eq!(chip.take_transmitted().len(), 1);
+    }
+
+    #[test]
+    fn send_raw_packet_reports_status() {
+        let (chip, mut eth) = setup();
+        let mut packet = tx::TxPacket::new();
+        packet.update_frame(&FRAME[..42], 42);
+        chip.set_tx_status(tx::etxstat::DEFER | 2);
+        let status = eth.send_raw_packet(&packet).unwrap();
+        assert_eq!(status.get_collision_count(), 2);
+        assert!(status.is_deferred());
+        assert!(status.is_crc_ok());
+        assert!(!status.is_aborted());
+        // Padded to 60 bytes, plus the FCS
+        assert_eq!(status.get_wire_length(), 64);
+        chip.set_tx_status(tx::etxstat::MAXCOL | 15);
+        match eth.send_raw_packet(&packet) {
+            Err(EthControllerError::TxAborted(status)) => {
+                assert!(status.is_max_collision_abort());
+                assert!(!status.is_late_collision());
+                assert_eq!(status.get_collision_count(), 15);
+            },
+            _ => panic!("expected TxAborted"),
+        }
+        // TXABTIF (EIR<2>) is cleared
+        assert_eq!(chip.read_reg(spi::addrs::EIR) & 0x0004, 0);
+        chip.set_tx_status(0);
+        assert!(eth.send_raw_packet(&packet).is_ok());
+        assert_eq!(chip.take_transmitted().len(), 2);
+    }
+
+    #[test]
+    fn tx_queue_stages_frames() {
+        let (chip, mut eth) = setup();
+        chip.set_tx_latency(3);
+        let mut frames = [FRAME; 3];
+        let mut packets = [tx::TxPacket::new(), tx::TxPacket::new(), tx::TxPacket::new()];
+        for (i, (frame, packet)) in frames.iter_mut().zip(packets.iter_mut()).enumerate() {
+            frame[20] = i as u8;
+            packet.update_frame(&frame[..], frame.len());
+            assert!(eth.queue_packet(packet).is_ok());
+        }
+        // The first frame is on the wire and the others are staged behind it
+        assert_eq!(chip.read_reg(spi::addrs::ETXST), 0);
+        assert_eq!(chip.read_sram(2 * FRAME.len() as u16, FRAME.len()), &frames[2][..]);
+        let mut completed = 0;
+        for _ in 0..100 {
+            if let Some(status) = eth.service_tx_queue().unwrap() {
+                assert!(status.is_crc_ok());
+                completed += 1;
+            }
+        }
+        assert_eq!(completed, 3);
+        let sent = chip.take_transmitted();
+        assert_eq!(sent.len(), 3);
+        assert!(sent.iter().zip(frames.iter()).all(|(sent, frame)| sent[..] == frame[..]));
+        assert_eq!(eth.service_tx_queue().unwrap(), None);
+    }
+
+    #[test]
+    fn tx_queue_never_splits_frames() {
+        let chip = Enc424j600::new();
+        let layout = MemoryLayout { tx_size: MemoryLayout::MIN_REGION_SIZE, user_size: 0 };
+        let mut eth = SpiEth::with_memory_layout(chip.spi(), chip.nss(), layout).unwrap();
+        assert!(eth.init_dev(&mut SimDelay).is_ok());
+        assert!(eth.init_rxbuf().is_ok());
+        assert!(eth.init_txbuf().is_ok());
+        chip.set_tx_latency(5);
+        let mut frame = [0xa5; 600];
+        frame[..6].copy_from_slice(&[0xff; 6]);
+        let mut packet = tx::TxPacket::new();
+        packet.update_frame(&frame, frame.len());
+        assert!(eth.queue_packet(&packet).is_ok());
+        assert!(eth.queue_packet(&packet).is_ok());
+        // A third frame would cross the end of the TX region, and its start is in use
+        assert!(matches!(eth.queue_packet(&packet), Err(EthControllerError::TxQueueFull)));
+        let mut big = tx::TxPacket::new();
+        big.update_frame(&[0; 1600], 1600);
+        assert!(matches!(eth.queue_packet(&big), Err(EthControllerError::FrameTooLarge { .. })));
+        // Completing the first frame frees the start of the region and starts the second
+        while eth.service_tx_queue().unwrap().is_none() {}
+        frame[20] = 0x01;
+        packet.update_frame(&frame, frame.len());
+        assert!(eth.queue_packet(&packet).is_ok());
+        // Wrapped to the start of the TX region instead of being split
+        assert_eq!(chip.read_sram(0, frame.len()), &frame[..]);
+        for _ in 0..100 {
+            eth.service_tx_queue().unwrap();
+        }
+        let sent = chip.take_transmitted();
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[2], &frame[..]);
+    }
+
+    #[test]
+    fn non_blocking_transmit() {
+        let (chip, mut eth) = setup();
+        let mut packet = tx::TxPacket::new();
+        packet.update_frame(&FRAME, FRAME.len());
+        assert!(matches!(eth.poll_transmit(), Err(nb::Error::Other(EthControllerError::NoTxPacket))));
+        chip.set_tx_latency(3);
+        assert!(eth.start_transmit(&packet).is_ok());
+        assert_eq!(chip.read_reg(spi::addrs::ECON1) & 0x0002, 0x0002);
+        let mut polls = 0;
+        let status = loop {
+            match eth.poll_transmit() {
+                Ok(status) => break status,
+                Err(nb::Error::WouldBlock) => polls += 1,
+                Err(nb::Error::Other(e)) => panic!("{}", e),
+            }
+        };
+        assert!(polls > 0);
+        assert_eq!(status.get_wire_length(), 64);
+        assert_eq!(chip.take_transmitted().len(), 1);
+        chip.set_tx_status(tx::etxstat::LATECOL);
+        assert!(eth.start_transmit(&packet).is_ok());
+        assert!(matches!(nb::block!(eth.poll_transmit()),
+            Err(EthControllerError::TxAborted(status)) if status.is_late_collision()));
+        // WouldBlock once every slot of the TX queue is taken
+        chip.set_tx_latency(u32::MAX);
+        for _ in 0..tx::TX_QUEUE_LENGTH {
+            assert!(eth.start_transmit(&packet).is_ok());
+        }
+        assert!(matches!(eth.start_transmit(&packet), Err(nb::Error::WouldBlock)));
+    }
+
+    #[test]
+    fn dma_copy_wraps_in_rx_ring() {
+        let (chip, mut eth) = setup();
+        let mut data = [0; 32];
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = i as u8 + 1;
+        }
+        // Straddle the end of SRAM; the second half sits at ERXST
+        chip.write_sram(rx::RX_MAX_ADDRESS - 15, &data[..16]);
+        chip.write_sram(rx::ERXST_DEFAULT, &data[16..]);
+        assert!(eth.dma_copy(rx::RX_MAX_ADDRESS - 15, 0x0100, 32).is_ok());
+        assert_eq!(chip.read_sram(0x0100, 32), &data[..]);
+        // Copy back into the RX ring across the wrap
+        assert!(eth.dma_copy(0x0100, rx::RX_MAX_ADDRESS - 7, 32).is_ok());
+        assert_eq!(chip.read_sram(rx::RX_MAX_ADDRESS - 7, 8), &data[..8]);
+        assert_eq!(chip.read_sram(rx::ERXST_DEFAULT, 24), &data[8..]);
+        // DMAIF (EIR<5>) signals completion
+        assert_eq!(chip.read_reg(spi::addrs::EIR) & 0x0020, 0x0020);
+        for &(src, dst, len) in [(0x0100, 0x0200, 0), (0x0100, 0x0200, 0x6000),
+                                 (0x0100, 0x6000, 1), (0x5400, 0x0000, 0x1000)].iter() {
+            assert!(matches!(eth.dma_copy(src, dst, len),
+                Err(EthControllerError::InvalidSramRange { .. })));
+        }
+    }
+
+    #[test]
+    fn dma_copy_polled() {
+        let (chip, mut eth) = setup();
+        chip.write_sram(0x0000, &FRAME);
+        chip.set_dma_latency(3);
+        assert!(eth.start_dma_copy(0x0000, 0x1000, FRAME.len() as u16).is_ok());
+        assert!(matches!(eth.start_dma_copy(0x0000, 0x2000, 1), Err(nb::Error::WouldBlock)));
+        assert!(matches!(eth.poll_dma(), Err(nb::Error::WouldBlock)));
+        assert!(nb::block!(eth.poll_dma()).is_ok());
+        assert_eq!(chip.read_sram(0x1000, FRAME.len()), &FRAME[..]);
+        chip.set_dma_latency(u32::MAX);
+        eth.set_poll_limits(PollLimits { dma_polls: 10, ..PollLimits::default() });
+        assert!(matches!(eth.dma_copy(0x0000, 0x2000, 1), Err(EthControllerError::DmaTimeout)));
+    }
+
+    #[test]
+    fn dma_checksum() {
+        let (chip, mut eth) = setup();
+        // IPv4 header with its checksum field cleared
+        let header = [0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11,
+                      0x00, 0x00, 0xc0, 0xa8, 0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7];
+        chip.write_sram(0x0200, &header);
+        assert_eq!(eth.dma_checksum(0x0200, 20, None).ok(), Some(0xb861));
+        // Seeding with the checksum of the first part continues it
+        let first = eth.dma_checksum(0x0200, 10, None).unwrap();
+        assert_eq!(eth.dma_checksum(0x020a, 10, Some(first)).ok(), Some(0xb861));
+        // An odd trailing byte is padded with zero
+        chip.write_sram(0x0300, &[0x01]);
+        assert_eq!(eth.dma_checksum(0x0300, 1, None).ok(), Some(0xfeff));
+        // Across the end of the RX ring
+        chip.write_sram(rx::RX_MAX_ADDRESS - 9, &header[..10]);
+        chip.write_sram(rx::ERXST_DEFAULT, &header[10..]);
+        assert_eq!(eth.dma_checksum(rx::RX_MAX_ADDRESS - 9, 20, None).ok(), Some(0xb861));
+        // The copy engine leaves EDMACS untouched
+        assert!(eth.dma_copy(0x0200, 0x0400, 20).is_ok());
+        assert_eq!(chip.read_reg(spi::addrs::EDMACS), 0xb861);
+    }
+
+    #[test]
+    fn tx_checksum_offload() {
+        let (chip, mut eth) = setup();
+        let frame = udp_frame();
+        let mut packet = tx::TxPacket::new();
+        packet.update_frame(&frame, frame.len());
+        packet.set_checksum_offload(true);
+        assert!(eth.send_raw_packet(&packet).is_ok());
+        let sent = chip.take_transmitted().pop().unwrap();
+        assert_eq!(checksum::checksum(&sent[14..34], None), 0);
+        let mut pseudo = [0; 12];
+        pseudo[..8].copy_from_slice(&sent[26..34]);
+        pseudo[9] = 17;
+        pseudo[11] = 13;
+        let seed = checksum::checksum(&pseudo, None);
+        assert_ne!(&sent[40..42], &[0, 0]);
+        assert_eq!(checksum::checksum(&sent[34..47], Some(seed)), 0);
+        assert_eq!(&sent[42..], &frame[42..]);
+        // Without offload the frame is sent as is
+        packet.set_checksum_offload(false);
+        assert!(eth.send_raw_packet(&packet).is_ok());
+        assert_eq!(chip.take_transmitted().pop().unwrap(), &frame[..]);
+    }
+
+    #[test]
+    fn rx_checksum_offload() {
+        let (chip, mut eth) = setup();
+        // Send the frame with TX checksum offload to get valid checksums
+        let frame = udp_frame();
+        let mut packet = tx::TxPacket::new();
+        packet.update_frame(&frame, frame.len());
+        packet.set_checksum_offload(true);
+        assert!(eth.send_raw_packet(&packet).is_ok());
+        let valid = chip.take_transmitted().pop().unwrap();
+        let mut bad_udp = valid.clone();
+        bad_udp[44] ^= 0x01;
+        let mut bad_ipv4 = valid.clone();
+        bad_ipv4[22] ^= 0x01;
+        // Corrupted frames are delivered while the offload is off
+        assert!(chip.receive(&bad_udp));
+        assert_eq!(eth.receive_next(false).unwrap().get_payload(), &bad_udp[..]);
+        eth.set_rx_checksum_offload(true);
+        for frame in [&bad_udp, &bad_ipv4].iter() {
+            assert!(chip.receive(frame));
+            assert!(matches!(eth.receive_next(false), Err(EthControllerError::NoRxPacket)));
+        }
+        assert_eq!(eth.get_rx_error_counters().checksum_error, 2);
+        // A dropped frame is skipped over to the next one
+        assert!(chip.receive(&bad_udp));
+        assert!(chip.receive(&valid));
+        assert_eq!(eth.receive_next(false).unwrap().get_payload(), &valid[..]);
+        assert_eq!(eth.get_rx_error_counters().checksum_error, 3);
+        // UDP without checksum, and non-IP frames, are not checked
+        let mut no_udp_checksum = valid.clone();
+        no_udp_checksum[40..42].copy_from_slice(&[0, 0]);
+        for frame in [&no_udp_checksum[..], &FRAME[..]].iter() {
+            assert!(chip.receive(frame));
+            assert_eq!(eth.receive_next(false).unwrap().get_payload(), *frame);
+        }
+        // IP frames with checksums out of reach are dropped as unverified
+        let mut fragment = valid.clone();
+        fragment[20] |= 0x20;
+        let mut hop_by_hop = [0; 14 + 40 + 8];
+        hop_by_hop[..6].copy_from_slice(&[0xff; 6]);
+        hop_by_hop[12..14].copy_from_slice(&[0x86, 0xdd]);
+        hop_by_hop[14..22].copy_from_slice(&[0x60, 0x00, 0x00, 0x00, 0x00, 0x08, 0x00, 0x01]);
+        hop_by_hop[54] = 58;
+        for frame in [&fragment[..], &hop_by_hop[..]].iter() {
+            assert!(chip.receive(frame));
+            assert!(matches!(eth.receive_next(false), Err(EthControllerError::NoRxPacket)));
+        }
+        assert_eq!(eth.get_rx_error_counters().checksum_unverified, 2);
+        assert_eq!(eth.get_rx_error_counters().checksum_error, 3);
+        // Checksums are verified in place wherever the frame wraps in the ring
+        let mut straddled = 0;
+        for i in 0..200 {
+            let pad_len = 60 + (i * 7) % 41;
+            let mut pad = [0xff; 100];
+            pad[12..14].copy_from_slice(&[0x88, 0xb5]);
+            assert!(chip.receive(&pad[..pad_len]));
+            assert_eq!(eth.receive_next(false).unwrap().get_payload(), &pad[..pad_len]);
+            let head = chip.read_reg(spi::addrs::ERXHEAD) as usize;
+            if head + 2 + rx::RSV_LENGTH + valid.len() + 4 > rx::RX_MAX_ADDRESS as usize + 1 {
+                straddled += 1;
+            }
+            let frame = if i % 3 == 0 { &bad_udp } else { &valid };
+            assert!(chip.receive(frame));
+            match eth.receive_next(false) {
+                Ok(packet) => assert!(i % 3 != 0 && packet.get_payload() == &valid[..]),
+                Err(_) => assert_eq!(i % 3, 0),
+            }
+        }
+        assert!(straddled >= 2);
+        assert_eq!(eth.get_rx_error_counters().checksum_error, 3 + 67);
+    }
+
+    #[test]
+    fn set_promiscuous_accepts_foreign_unicast() {
+        let (chip, mut eth) = setup();
+        let mut frame = FRAME;
+        frame[..6].copy_from_slice(&[0x02, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        assert!(!chip.receive(&frame));
+        assert!(eth.set_promiscuous().is_ok());
+        assert!(chip.receive(&frame));
+        assert!(eth.receive_next(false).is_ok());
+    }
 }
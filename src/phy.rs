@@ -0,0 +1,168 @@
+use bitflags::bitflags;
+
+/// PHY Register Addresses, accessed via MII management (MIREGADR)
+/// See: Table 3-4, ENC424J600 Data Sheet
+pub mod addrs {
+    pub const PHCON1: u8 = 0x00;
+    pub const PHSTAT1: u8 = 0x01;
+    pub const PHANA: u8 = 0x04;
+    pub const PHANLPA: u8 = 0x05;
+    pub const PHANE: u8 = 0x06;
+    pub const PHCON2: u8 = 0x11;
+    pub const PHSTAT2: u8 = 0x1b;
+    pub const PHSTAT3: u8 = 0x1f;
+}
+
+/// Bits 12:8 of MIREGADR must always be written as 0b00001
+pub const MIREGADR_RESERVED: u16 = 0x0100;
+
+bitflags! {
+    /// PHY Control Register 1
+    /// See: Register 3-1, ENC424J600 Data Sheet
+    pub struct Phcon1: u16 {
+        const PRST = 1 << 15;
+        const PLOOPBK = 1 << 14;
+        const SPD100 = 1 << 13;
+        const ANEN = 1 << 12;
+        const PSLEEP = 1 << 11;
+        const RENEG = 1 << 9;
+        const PFULDPX = 1 << 8;
+    }
+}
+
+bitflags! {
+    /// PHY Status Register 1
+    /// See: Register 3-2, ENC424J600 Data Sheet
+    pub struct Phstat1: u16 {
+        const FULL100 = 1 << 14;
+        const HALF100 = 1 << 13;
+        const FULL10 = 1 << 12;
+        const HALF10 = 1 << 11;
+        const ANDONE = 1 << 5;
+        const LRFAULT = 1 << 4;
+        const ANABLE = 1 << 3;
+        const LLSTAT = 1 << 2;
+        const EXTREGS = 1 << 0;
+    }
+}
+
+bitflags! {
+    /// PHY Auto-Negotiation Advertisement Register
+    /// See: Register 3-3, ENC424J600 Data Sheet
+    pub struct Phana: u16 {
+        const ADNP = 1 << 15;
+        const ADFAULT = 1 << 13;
+        const ADPAUS1 = 1 << 11;
+        const ADPAUS0 = 1 << 10;
+        const AD100FD = 1 << 8;
+        const AD100 = 1 << 7;
+        const AD10FD = 1 << 6;
+        const AD10 = 1 << 5;
+        /// Selector field ADIEEE<4:0>, must be 0b00001 (IEEE 802.3)
+        const ADIEEE = 0b0_0001;
+    }
+}
+
+bitflags! {
+    /// PHY Auto-Negotiation Link Partner Ability Register
+    /// See: Register 3-4, ENC424J600 Data Sheet
+    pub struct Phanlpa: u16 {
+        const LPNP = 1 << 15;
+        const LPACK = 1 << 14;
+        const LPFAULT = 1 << 13;
+        const LPPAUS1 = 1 << 11;
+        const LPPAUS0 = 1 << 10;
+        const LP100T4 = 1 << 9;
+        const LP100FD = 1 << 8;
+        const LP100 = 1 << 7;
+        const LP10FD = 1 << 6;
+        const LP10 = 1 << 5;
+        /// Selector field LPIEEE<4:0>
+        const LPIEEE = 0b1_1111;
+    }
+}
+
+bitflags! {
+    /// PHY Auto-Negotiation Expansion Register
+    /// See: Register 3-5, ENC424J600 Data Sheet
+    pub struct Phane: u16 {
+        const PDFLT = 1 << 4;
+        const LPARCD = 1 << 1;
+        const LPANABL = 1 << 0;
+    }
+}
+
+bitflags! {
+    /// PHY Status Register 2
+    /// See: Register 3-7, ENC424J600 Data Sheet
+    pub struct Phstat2: u16 {
+        const PLRITY = 1 << 4;
+    }
+}
+
+bitflags! {
+    /// PHY Status Register 3
+    /// See: Register 3-8, ENC424J600 Data Sheet
+    pub struct Phstat3: u16 {
+        /// Speed and duplex field SPDDPX<2:0>, see `Phstat3::speed_duplex`
+        const SPDDPX = 0b111 << 2;
+    }
+}
+
+/// Link speed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Speed {
+    Mbps10,
+    Mbps100,
+}
+
+/// Link duplex mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Duplex {
+    Half,
+    Full,
+}
+
+impl Phstat3 {
+    /// Decode SPDDPX<2:0> into the current speed and duplex, if valid
+    pub fn speed_duplex(&self) -> Option<(Speed, Duplex)> {
+        match (self.bits() & Self::SPDDPX.bits()) >> 2 {
+            0b001 => Some((Speed::Mbps10, Duplex::Half)),
+            0b010 => Some((Speed::Mbps100, Duplex::Half)),
+            0b101 => Some((Speed::Mbps10, Duplex::Full)),
+            0b110 => Some((Speed::Mbps100, Duplex::Full)),
+            _ => None,
+        }
+    }
+}
+
+/// Link status reported by `EthController::link_status`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkStatus {
+    Down,
+    Up { speed: Speed, duplex: Duplex },
+}
+
+impl LinkStatus {
+    pub fn is_up(&self) -> bool {
+        matches!(self, LinkStatus::Up { .. })
+    }
+}
+
+/// Link configuration applied by `EthController::init_dev`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkConfig {
+    /// Auto-negotiate, advertising the given abilities in PHANA
+    AutoNegotiate(Phana),
+    /// Disable auto-negotiation and force the given speed and duplex
+    Forced { speed: Speed, duplex: Duplex },
+}
+
+impl Default for LinkConfig {
+    /// Auto-negotiate with the reset abilities of PHANA (0x05E1): all speeds and
+    /// duplex modes, and symmetric PAUSE
+    fn default() -> Self {
+        LinkConfig::AutoNegotiate(Phana::ADPAUS0 | Phana::AD100FD | Phana::AD100 |
+                                  Phana::AD10FD | Phana::AD10)
+    }
+}
@@ -1,4 +1,4 @@
-use crate::RAW_FRAME_LENGTH_MAX;
+use crate::{MacAddress, RAW_FRAME_LENGTH_MAX};
 
 /// SRAM Addresses
 pub const ERXST_DEFAULT: u16 = 0x5340;
@@ -7,6 +7,339 @@ pub const RX_MAX_ADDRESS: u16 = 0x5fff;
 
 /// Receive Status Vector Length
 pub const RSV_LENGTH: usize = 6;
+/// Length of the Frame Check Sequence at the end of each received frame
+pub const FCS_LENGTH: usize = 4;
+
+/// ERXFCON Bits
+/// See: Section 10, ENC424J600 Data Sheet
+pub mod erxfcon {
+    pub const HTEN: u16 = 1 << 15;
+    pub const MPEN: u16 = 1 << 14;
+    pub const NOTPM: u16 = 1 << 12;
+    pub const PMEN_MASK: u16 = 0b1111 << 8;
+    pub const PMEN_SHIFT: u16 = 8;
+    pub const CRCEEN: u16 = 1 << 7;
+    pub const CRCEN: u16 = 1 << 6;
+    pub const RUNTEEN: u16 = 1 << 5;
+    pub const RUNTEN: u16 = 1 << 4;
+    pub const UCEN: u16 = 1 << 3;
+    pub const NOTMEEN: u16 = 1 << 2;
+    pub const MCEN: u16 = 1 << 1;
+    pub const BCEN: u16 = 1 << 0;
+}
+
+/// Receive Status Vector Bits
+/// See: Table 9-1, ENC424J600 Data Sheet
+pub mod rsv {
+    pub const BYTE_COUNT_MASK: u64 = 0xffff;
+    pub const PREVIOUSLY_IGNORED: u64 = 1 << 16;
+    pub const CARRIER_EVENT: u64 = 1 << 18;
+    pub const CRC_ERROR: u64 = 1 << 20;
+    pub const LENGTH_CHECK_ERROR: u64 = 1 << 21;
+    pub const LENGTH_OUT_OF_RANGE: u64 = 1 << 22;
+    pub const RECEIVED_OK: u64 = 1 << 23;
+    pub const MULTICAST: u64 = 1 << 24;
+    pub const BROADCAST: u64 = 1 << 25;
+    pub const DRIBBLE_NIBBLE: u64 = 1 << 26;
+    pub const CONTROL_FRAME: u64 = 1 << 27;
+    pub const PAUSE_FRAME: u64 = 1 << 28;
+    pub const UNKNOWN_OPCODE: u64 = 1 << 29;
+    pub const VLAN: u64 = 1 << 30;
+    pub const RUNT_MATCH: u64 = 1 << 31;
+    pub const NOT_ME_MATCH: u64 = 1 << 32;
+    pub const HASH_MATCH: u64 = 1 << 33;
+    pub const MAGIC_PACKET_MATCH: u64 = 1 << 34;
+    pub const PATTERN_MATCH: u64 = 1 << 35;
+    pub const UNICAST_MATCH: u64 = 1 << 36;
+    pub const BROADCAST_MATCH: u64 = 1 << 37;
+    pub const MULTICAST_MATCH: u64 = 1 << 38;
+}
+
+/// Pattern match filter mode, PMEN<3:0> (ERXFCON<11:8>)
+/// Every mode except `Disabled` requires the pattern match checksum to match
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatternMatchMode {
+    Disabled = 0b0000,
+    Checksum = 0b0001,
+    Unicast = 0b0010,
+    NotUnicast = 0b0011,
+    Multicast = 0b0100,
+    NotMulticast = 0b0101,
+    Broadcast = 0b0110,
+    NotBroadcast = 0b0111,
+    HashTable = 0b1000,
+    MagicPacket = 0b1001,
+}
+
+impl PatternMatchMode {
+    fn from_bits(bits: u16) -> Option<Self> {
+        match bits {
+            0b0000 => Some(PatternMatchMode::Disabled),
+            0b0001 => Some(PatternMatchMode::Checksum),
+            0b0010 => Some(PatternMatchMode::Unicast),
+            0b0011 => Some(PatternMatchMode::NotUnicast),
+            0b0100 => Some(PatternMatchMode::Multicast),
+            0b0101 => Some(PatternMatchMode::NotMulticast),
+            0b0110 => Some(PatternMatchMode::Broadcast),
+            0b0111 => Some(PatternMatchMode::NotBroadcast),
+            0b1000 => Some(PatternMatchMode::HashTable),
+            0b1001 => Some(PatternMatchMode::MagicPacket),
+            _ => None,
+        }
+    }
+}
+
+/// Length of the pattern match window, one byte per bit of EPMM1-EPMM4
+pub const PATTERN_WINDOW_LENGTH: usize = 64;
+
+/// Pattern match checksum (EPMCS) over the bytes of `pattern` selected by `mask`
+/// Like the hardware, the selected bytes are summed as consecutive big-endian
+/// 16-bit words (an odd last byte is padded with zero) in ones' complement.
+pub fn pattern_match_checksum(pattern: &[u8], mask: u64) -> u16 {
+    let mut sum = 0_u32;
+    let selected = pattern.iter()
+        .take(PATTERN_WINDOW_LENGTH)
+        .enumerate()
+        .filter(|(i, _)| mask & (1 << i) != 0)
+        .map(|(_, &byte)| byte);
+    let mut hi = None;
+    for byte in selected {
+        match hi.take() {
+            None => hi = Some(byte),
+            Some(hi_byte) => sum += ((hi_byte as u32) << 8) | byte as u32,
+        }
+    }
+    if let Some(hi_byte) = hi {
+        sum += (hi_byte as u32) << 8;
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Policy for frames with a CRC error or runt frames
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorFramePolicy {
+    /// Accept the frames if another filter accepts them
+    Accept,
+    /// Discard the frames (CRCEN/RUNTEN)
+    Reject,
+    /// Accept only such frames and discard all others (CRCEEN/RUNTEEN)
+    AcceptOnly,
+}
+
+/// RX filter configuration, as programmed into ERXFCON
+/// A frame is accepted if any enabled filter accepts it,
+/// and no enabled rejection policy discards it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RxFilter {
+    erxfcon: u16
+}
+
+impl Default for RxFilter {
+    /// Reset value of ERXFCON: unicast and broadcast, rejecting CRC errors and runts
+    fn default() -> Self {
+        RxFilter { erxfcon: 0x0059 }
+    }
+}
+
+impl RxFilter {
+    /// A filter that accepts nothing
+    pub fn new() -> Self {
+        RxFilter { erxfcon: 0 }
+    }
+
+    /// Accept all frames regardless of content, except those with CRC errors or runts
+    /// See: Section 10.12, ENC424J600 Data Sheet
+    pub fn promiscuous() -> Self {
+        RxFilter::new()
+            .unicast(true)
+            .not_me(true)
+            .multicast(true)
+            .crc_policy(ErrorFramePolicy::Reject)
+            .runt_policy(ErrorFramePolicy::Reject)
+    }
+
+    pub fn from_bits(erxfcon: u16) -> Self {
+        RxFilter { erxfcon }
+    }
+    pub fn bits(&self) -> u16 {
+        self.erxfcon
+    }
+
+    fn with_bit(mut self, bit: u16, enable: bool) -> Self {
+        if enable { self.erxfcon |= bit } else { self.erxfcon &= !bit }
+        self
+    }
+
+    /// Accept unicast frames to the station address (UCEN)
+    pub fn unicast(self, enable: bool) -> Self {
+        self.with_bit(erxfcon::UCEN, enable)
+    }
+    pub fn is_unicast(&self) -> bool {
+        self.erxfcon & erxfcon::UCEN != 0
+    }
+
+    /// Accept unicast frames to any other address (NOTMEEN)
+    pub fn not_me(self, enable: bool) -> Self {
+        self.with_bit(erxfcon::NOTMEEN, enable)
+    }
+    pub fn is_not_me(&self) -> bool {
+        self.erxfcon & erxfcon::NOTMEEN != 0
+    }
+
+    /// Accept all multicast frames (MCEN)
+    pub fn multicast(self, enable: bool) -> Self {
+        self.with_bit(erxfcon::MCEN, enable)
+    }
+    pub fn is_multicast(&self) -> bool {
+        self.erxfcon & erxfcon::MCEN != 0
+    }
+
+    /// Accept broadcast frames (BCEN)
+    pub fn broadcast(self, enable: bool) -> Self {
+        self.with_bit(erxfcon::BCEN, enable)
+    }
+    pub fn is_broadcast(&self) -> bool {
+        self.erxfcon & erxfcon::BCEN != 0
+    }
+
+    /// Accept frames whose destination hits the hash table in EHT1-EHT4 (HTEN)
+    pub fn hash_table(self, enable: bool) -> Self {
+        self.with_bit(erxfcon::HTEN, enable)
+    }
+    pub fn is_hash_table(&self) -> bool {
+        self.erxfcon & erxfcon::HTEN != 0
+    }
+
+    /// Accept Magic Packets for the station address (MPEN)
+    pub fn magic_packet(self, enable: bool) -> Self {
+        self.with_bit(erxfcon::MPEN, enable)
+    }
+    pub fn is_magic_packet(&self) -> bool {
+        self.erxfcon & erxfcon::MPEN != 0
+    }
+
+    /// Select the pattern match filter mode (PMEN<3:0>)
+    pub fn pattern_match(mut self, mode: PatternMatchMode) -> Self {
+        self.erxfcon = (self.erxfcon & !erxfcon::PMEN_MASK) | ((mode as u16) << erxfcon::PMEN_SHIFT);
+        self
+    }
+    /// None if PMEN<3:0> holds a reserved value
+    pub fn get_pattern_match(&self) -> Option<PatternMatchMode> {
+        PatternMatchMode::from_bits((self.erxfcon & erxfcon::PMEN_MASK) >> erxfcon::PMEN_SHIFT)
+    }
+
+    /// Require the pattern match checksum to NOT match (NOTPM)
+    pub fn pattern_match_inverted(self, enable: bool) -> Self {
+        self.with_bit(erxfcon::NOTPM, enable)
+    }
+    pub fn is_pattern_match_inverted(&self) -> bool {
+        self.erxfcon & erxfcon::NOTPM != 0
+    }
+
+    /// Policy for frames with a CRC error (CRCEN, CRCEEN)
+    pub fn crc_policy(self, policy: ErrorFramePolicy) -> Self {
+        self.with_bit(erxfcon::CRCEN, policy == ErrorFramePolicy::Reject)
+            .with_bit(erxfcon::CRCEEN, policy == ErrorFramePolicy::AcceptOnly)
+    }
+    pub fn get_crc_policy(&self) -> ErrorFramePolicy {
+        Self::error_policy(self.erxfcon, erxfcon::CRCEN, erxfcon::CRCEEN)
+    }
+
+    /// Policy for runt frames shorter than 64 bytes (RUNTEN, RUNTEEN)
+    pub fn runt_policy(self, policy: ErrorFramePolicy) -> Self {
+        self.with_bit(erxfcon::RUNTEN, policy == ErrorFramePolicy::Reject)
+            .with_bit(erxfcon::RUNTEEN, policy == ErrorFramePolicy::AcceptOnly)
+    }
+    pub fn get_runt_policy(&self) -> ErrorFramePolicy {
+        Self::error_policy(self.erxfcon, erxfcon::RUNTEN, erxfcon::RUNTEEN)
+    }
+
+    fn error_policy(erxfcon: u16, reject: u16, accept_only: u16) -> ErrorFramePolicy {
+        if erxfcon & accept_only != 0 {
+            ErrorFramePolicy::AcceptOnly
+        } else if erxfcon & reject != 0 {
+            ErrorFramePolicy::Reject
+        } else {
+            ErrorFramePolicy::Accept
+        }
+    }
+}
+
+/// Ethernet CRC-32 (IEEE 802.3), as appended in the FCS
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffff_u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Bit of the 64-bit hash table (EHT1-EHT4) that a destination address maps to
+/// The hardware uses bits 28:23 of the CRC-32 over the address computed MSB-first
+/// (polynomial 0x04C11DB7), before the final inversion: the bit-reversed `crc32` register.
+pub fn hash_table_bit(addr: &MacAddress) -> u8 {
+    (((!crc32(addr.as_bytes())).reverse_bits() >> 23) & 0x3f) as u8
+}
+
+/// Multicast hash table with a reference count per bit,
+/// so that addresses sharing a bit do not evict each other
+pub struct HashTable {
+    refs: [u16; 64]
+}
+
+impl Default for HashTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HashTable {
+    pub fn new() -> Self {
+        HashTable {
+            refs: [0; 64]
+        }
+    }
+
+    /// Count a reference to the bit of `addr`; returns the bit if it has just been set
+    pub fn add(&mut self, addr: &MacAddress) -> Option<u8> {
+        let bit = hash_table_bit(addr);
+        self.refs[bit as usize] = self.refs[bit as usize].saturating_add(1);
+        if self.refs[bit as usize] == 1 { Some(bit) } else { None }
+    }
+
+    /// Drop a reference to the bit of `addr`; returns the bit if it has just been cleared
+    pub fn remove(&mut self, addr: &MacAddress) -> Option<u8> {
+        let bit = hash_table_bit(addr);
+        match self.refs[bit as usize] {
+            0 => None,
+            1 => {
+                self.refs[bit as usize] = 0;
+                Some(bit)
+            },
+            _ => {
+                self.refs[bit as usize] -= 1;
+                None
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.refs = [0; 64];
+    }
+
+    /// The 16-bit word of EHT1-EHT4 (0 to 3) that holds `bit`
+    pub fn word(&self, bit: u8) -> u16 {
+        let first = (bit as usize / 16) * 16;
+        (0..16).filter(|i| self.refs[first + i] > 0)
+            .fold(0, |word, i| word | (1 << i))
+    }
+}
 
 /// Struct for RX Buffer on the hardware
 /// TODO: Should be a singleton
@@ -16,6 +349,12 @@ pub struct RxBuffer {
     tail_addr: u16
 }
 
+impl Default for RxBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RxBuffer {
     pub fn new() -> Self {
         RxBuffer {
@@ -45,20 +384,115 @@ impl RxBuffer {
     pub fn get_tail_addr(& self) -> u16{
         self.tail_addr
     }
+
+    /// ERXTAIL value that frees the ring up to `next_addr`:
+    /// two bytes behind it, or the last even address of SRAM if it is at the wrap address
+    pub fn tail_for(&self, next_addr: u16) -> u16 {
+        if next_addr > self.wrap_addr {
+            next_addr - 2
+        } else {
+            RX_MAX_ADDRESS - 1
+        }
+    }
+
+    /// Address `offset` bytes after `addr` in the ring, wrapping from the end of SRAM
+    /// back to the wrap address as RERXDATA and the DMA engine do
+    pub fn wrapping_add(&self, addr: u16, offset: u16) -> u16 {
+        let addr = addr as u32 + offset as u32;
+        if addr > RX_MAX_ADDRESS as u32 {
+            (addr - RX_MAX_ADDRESS as u32 - 1 + self.wrap_addr as u32) as u16
+        } else {
+            addr as u16
+        }
+    }
+}
+
+/// Error in a received frame, reported in its Receive Status Vector
+/// or found by `EthController::set_rx_checksum_offload`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RxFrameError {
+    /// Frame had a CRC error
+    CrcError,
+    /// Length/type field is a length that does not match the data length
+    LengthCheckError,
+    /// Length/type field is larger than 1500 but is not a valid type
+    LengthOutOfRange,
+    /// An IPv4, TCP, UDP, ICMP or ICMPv6 checksum did not verify
+    ChecksumError,
+    /// An IP frame carries a checksum that could not be located to verify
+    ChecksumUnverified,
+}
+
+/// What `EthController::receive_next` does with frames whose RSV reports an error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RxErrorPolicy {
+    /// Deliver every frame without checking the RSV
+    DeliverAll,
+    /// Deliver every frame, with the error available from `RxPacket::get_error`
+    Flag,
+    /// Discard errored frames, counting them in `RxErrorCounters`
+    Drop,
+}
+
+// Deriving it would need `#[default]` on the variant, only available since Rust 1.62
+#[allow(clippy::derivable_impls)]
+impl Default for RxErrorPolicy {
+    fn default() -> Self {
+        RxErrorPolicy::DeliverAll
+    }
+}
+
+/// Number of frames discarded under `RxErrorPolicy::Drop`, per reason,
+/// and of frames failing RX checksum offload
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RxErrorCounters {
+    pub crc_error: u32,
+    pub length_check_error: u32,
+    pub length_out_of_range: u32,
+    pub checksum_error: u32,
+    pub checksum_unverified: u32,
+}
+
+impl RxErrorCounters {
+    pub fn count(&mut self, error: RxFrameError) {
+        let counter = match error {
+            RxFrameError::CrcError => &mut self.crc_error,
+            RxFrameError::LengthCheckError => &mut self.length_check_error,
+            RxFrameError::LengthOutOfRange => &mut self.length_out_of_range,
+            RxFrameError::ChecksumError => &mut self.checksum_error,
+            RxFrameError::ChecksumUnverified => &mut self.checksum_unverified,
+        };
+        *counter = counter.wrapping_add(1);
+    }
+    pub fn total(&self) -> u32 {
+        self.crc_error
+            .wrapping_add(self.length_check_error)
+            .wrapping_add(self.length_out_of_range)
+            .wrapping_add(self.checksum_error)
+            .wrapping_add(self.checksum_unverified)
+    }
 }
 
 /// Struct for RX Packet
 /// TODO: Generalise MAC addresses
 pub struct RxPacket {
     rsv: Rsv,
+    error: Option<RxFrameError>,
     frame: [u8; RAW_FRAME_LENGTH_MAX],
     frame_length: usize
 }
 
+impl Default for RxPacket {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RxPacket {
     pub fn new() -> Self {
         RxPacket {
             rsv: Rsv::new(),
+            error: None,
             frame: [0; RAW_FRAME_LENGTH_MAX],
             frame_length: 0
         }
@@ -71,6 +505,112 @@ impl RxPacket {
         self.rsv.read_raw_rsv()
     }
 
+    /// Frame had a CRC error
+    pub fn is_crc_error(&self) -> bool {
+        self.rsv.is_set(rsv::CRC_ERROR)
+    }
+    /// Length/type field is a length that does not match the data length
+    pub fn is_length_check_error(&self) -> bool {
+        self.rsv.is_set(rsv::LENGTH_CHECK_ERROR)
+    }
+    /// Length/type field is larger than 1500 but is not a valid type
+    pub fn is_length_out_of_range(&self) -> bool {
+        self.rsv.is_set(rsv::LENGTH_OUT_OF_RANGE)
+    }
+    /// Frame had a valid CRC and no symbol errors
+    pub fn is_received_ok(&self) -> bool {
+        self.rsv.is_set(rsv::RECEIVED_OK)
+    }
+    /// Destination address is a multicast address
+    pub fn is_multicast(&self) -> bool {
+        self.rsv.is_set(rsv::MULTICAST)
+    }
+    /// Destination address is the broadcast address
+    pub fn is_broadcast(&self) -> bool {
+        self.rsv.is_set(rsv::BROADCAST)
+    }
+    /// Extra bits were received after the frame
+    pub fn is_dribble_nibble(&self) -> bool {
+        self.rsv.is_set(rsv::DRIBBLE_NIBBLE)
+    }
+    /// Frame is a MAC control frame
+    pub fn is_control_frame(&self) -> bool {
+        self.rsv.is_set(rsv::CONTROL_FRAME)
+    }
+    /// Frame is a pause control frame
+    pub fn is_pause_frame(&self) -> bool {
+        self.rsv.is_set(rsv::PAUSE_FRAME)
+    }
+    /// Frame is a control frame with an unsupported opcode
+    pub fn is_unknown_opcode(&self) -> bool {
+        self.rsv.is_set(rsv::UNKNOWN_OPCODE)
+    }
+    /// Frame has a VLAN tag (type 0x8100)
+    pub fn is_vlan(&self) -> bool {
+        self.rsv.is_set(rsv::VLAN)
+    }
+    /// A frame was dropped by the filters since the last received frame
+    pub fn is_previously_ignored(&self) -> bool {
+        self.rsv.is_set(rsv::PREVIOUSLY_IGNORED)
+    }
+    /// A carrier event was seen since the last received frame
+    pub fn is_carrier_event(&self) -> bool {
+        self.rsv.is_set(rsv::CARRIER_EVENT)
+    }
+
+    /// First error reported in the RSV, if any
+    pub fn get_rsv_error(&self) -> Option<RxFrameError> {
+        if self.is_crc_error() {
+            Some(RxFrameError::CrcError)
+        } else if self.is_length_check_error() {
+            Some(RxFrameError::LengthCheckError)
+        } else if self.is_length_out_of_range() {
+            Some(RxFrameError::LengthOutOfRange)
+        } else {
+            None
+        }
+    }
+    /// Error flagged by `receive_next` under `RxErrorPolicy::Flag`
+    pub fn set_error(&mut self, error: Option<RxFrameError>) {
+        self.error = error;
+    }
+    pub fn get_error(&self) -> Option<RxFrameError> {
+        self.error
+    }
+
+    /// Frame was accepted by the unicast filter (UCEN)
+    pub fn is_unicast_match(&self) -> bool {
+        self.rsv.is_set(rsv::UNICAST_MATCH)
+    }
+    /// Frame was accepted by the pattern match filter (PMEN)
+    pub fn is_pattern_match(&self) -> bool {
+        self.rsv.is_set(rsv::PATTERN_MATCH)
+    }
+    /// Frame was accepted by the Magic Packet filter (MPEN)
+    pub fn is_magic_packet_match(&self) -> bool {
+        self.rsv.is_set(rsv::MAGIC_PACKET_MATCH)
+    }
+    /// Frame was accepted by the hash table filter (HTEN)
+    pub fn is_hash_match(&self) -> bool {
+        self.rsv.is_set(rsv::HASH_MATCH)
+    }
+    /// Frame was accepted by the not-me filter (NOTMEEN)
+    pub fn is_not_me_match(&self) -> bool {
+        self.rsv.is_set(rsv::NOT_ME_MATCH)
+    }
+    /// Frame was accepted by the runt filter (RUNTEEN)
+    pub fn is_runt_match(&self) -> bool {
+        self.rsv.is_set(rsv::RUNT_MATCH)
+    }
+    /// Frame was accepted by the broadcast filter (BCEN)
+    pub fn is_broadcast_match(&self) -> bool {
+        self.rsv.is_set(rsv::BROADCAST_MATCH)
+    }
+    /// Frame was accepted by the multicast filter (MCEN)
+    pub fn is_multicast_match(&self) -> bool {
+        self.rsv.is_set(rsv::MULTICAST_MATCH)
+    }
+
     pub fn update_frame_length(&mut self) {
         self.rsv.set_frame_length();
         self.frame_length = self.rsv.get_frame_length() as usize;
@@ -81,14 +621,30 @@ impl RxPacket {
     }
 
     pub fn copy_frame_from(&mut self, raw_frame: &[u8]) {
-        for i in 0..self.frame_length {
-            self.frame[i] = raw_frame[i];
-        }
+        self.frame[..self.frame_length].copy_from_slice(&raw_frame[..self.frame_length]);
     }
     pub fn write_frame_to(&self, frame: &mut [u8]) {
-        for i in 0..self.frame_length {
-            frame[i] = self.frame[i];
-        }
+        frame[..self.frame_length].copy_from_slice(&self.frame[..self.frame_length]);
+    }
+
+    /// Length of the frame without the trailing FCS
+    pub fn get_payload_length(&self) -> usize {
+        self.frame_length.saturating_sub(FCS_LENGTH)
+    }
+    /// Frame bytes without the trailing FCS
+    pub fn get_payload(&self) -> &[u8] {
+        &self.frame[..self.get_payload_length()]
+    }
+    pub fn write_payload_to(&self, payload: &mut [u8]) {
+        let len = self.get_payload_length();
+        payload[..len].copy_from_slice(&self.frame[..len]);
+    }
+    /// FCS as received, in the same form as `crc32` of the payload
+    pub fn get_fcs(&self) -> u32 {
+        let mut fcs = [0; FCS_LENGTH];
+        let start = self.get_payload_length();
+        fcs[..self.frame_length - start].copy_from_slice(&self.frame[start..self.frame_length]);
+        u32::from_le_bytes(fcs)
     }
 
     pub fn get_mut_frame(&mut self) -> &mut [u8] {
@@ -105,7 +661,6 @@ impl RxPacket {
 /// See: Table 9-1, ENC424J600 Data Sheet
 struct Rsv {
     raw_rsv: [u8; RSV_LENGTH],
-    // TODO: Add more definitions
     frame_length: u16
 }
 
@@ -118,16 +673,23 @@ impl Rsv {
     }
 
     fn write_to_rsv(&mut self, raw_rsv: &[u8]) {
-        for i in 0..RSV_LENGTH {
-            self.raw_rsv[i] = raw_rsv[i];
-        }
+        self.raw_rsv.copy_from_slice(&raw_rsv[..RSV_LENGTH]);
     }
     fn read_raw_rsv(&self) -> &[u8] {
         &self.raw_rsv
     }
 
+    fn bits(&self) -> u64 {
+        let mut bytes = [0; 8];
+        bytes[..RSV_LENGTH].copy_from_slice(&self.raw_rsv);
+        u64::from_le_bytes(bytes)
+    }
+    fn is_set(&self, mask: u64) -> bool {
+        self.bits() & mask != 0
+    }
+
     fn set_frame_length(&mut self) {
-        self.frame_length = (self.raw_rsv[0] as u16) | ((self.raw_rsv[1] as u16) << 8);
+        self.frame_length = (self.bits() & rsv::BYTE_COUNT_MASK) as u16;
     }
     fn get_frame_length(&self) -> u16 {
         self.frame_length
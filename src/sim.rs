@@ -0,0 +1,802 @@
+//! Software model of an ENC424J600 for host-side testing
+//!
+//! `Enc424j600` holds the chip state (SFR file, 24 KB SRAM, RX ring, TX engine
+//! and EIR/ESTAT flags). `SimSpi` and `SimNss` are handles to the same chip that
+//! implement the `embedded-hal` traits, so they can be passed to `SpiEth::new`
+//! while the test keeps the model to inject frames and inspect the results.
+extern crate std;
+
+use crate::{
+    phy::{self, Duplex, Phana, Phanlpa, Phcon1, Phstat1, Speed},
+    rx::{crc32, erxfcon, pattern_match_checksum, PATTERN_WINDOW_LENGTH},
+    spi::{addrs, opcodes},
+    tx::etxstat,
+};
+use core::{cell::RefCell, convert::Infallible};
+use embedded_hal::{
+    blocking::{
+        spi::Transfer,
+        delay::DelayUs,
+    },
+    digital::v2::OutputPin,
+};
+use std::{rc::Rc, vec::Vec};
+
+/// Size of the on-chip SRAM in bytes
+pub const SRAM_SIZE: usize = 0x6000;
+/// Number of unbanked SFR addresses reachable through RCRU/WCRU
+const SFR_SIZE: usize = 0xa0;
+
+/// Receive Status Vector bits written by the model, from Table 9-1 of the data sheet,
+/// kept independent of `rx::rsv` so that the driver is checked against them
+mod rsv {
+    pub const CRC_ERROR: u64 = 1 << 20;
+    pub const LENGTH_CHECK_ERROR: u64 = 1 << 21;
+    pub const LENGTH_OUT_OF_RANGE: u64 = 1 << 22;
+    pub const RECEIVED_OK: u64 = 1 << 23;
+    pub const MULTICAST: u64 = 1 << 24;
+    pub const BROADCAST: u64 = 1 << 25;
+    pub const CONTROL_FRAME: u64 = 1 << 27;
+    pub const PAUSE_FRAME: u64 = 1 << 28;
+    pub const UNKNOWN_OPCODE: u64 = 1 << 29;
+    pub const VLAN: u64 = 1 << 30;
+    pub const RUNT_MATCH: u64 = 1 << 31;
+    pub const NOT_ME_MATCH: u64 = 1 << 32;
+    pub const HASH_MATCH: u64 = 1 << 33;
+    pub const MAGIC_PACKET_MATCH: u64 = 1 << 34;
+    pub const PATTERN_MATCH: u64 = 1 << 35;
+    pub const UNICAST_MATCH: u64 = 1 << 36;
+    pub const BROADCAST_MATCH: u64 = 1 << 37;
+    pub const MULTICAST_MATCH: u64 = 1 << 38;
+}
+
+/// EHT bit of a destination address: bits 28:23 of its CRC-32 computed MSB-first,
+/// kept independent of `rx::hash_table_bit` so that the driver is checked against it
+fn hash_table_index(dest: &[u8]) -> u8 {
+    let mut crc = 0xffff_ffff_u32;
+    for &byte in dest {
+        for i in 0..8 {
+            let feedback = (crc >> 31) ^ ((byte as u32 >> i) & 1);
+            crc = (crc << 1) ^ if feedback != 0 { 0x04c1_1db7 } else { 0 };
+        }
+    }
+    ((crc >> 23) & 0x3f) as u8
+}
+
+/// Factory MAC address of the model
+pub const FACTORY_MAC: [u8; 6] = [0x04, 0x91, 0x62, 0x3e, 0xfc, 0x1e];
+
+/// SFR reset values
+/// See: SFR Summary, ENC424J600 Data Sheet
+const SFR_RESET: &[(u8, u16)] = &[
+    (addrs::ERXST, 0x5340),
+    (addrs::ERXTAIL, 0x5ffe),
+    (addrs::ERXHEAD, 0x5340),
+    (addrs::ERXRDPT, 0x5340),
+    (addrs::ERXFCON, 0x0059),
+    (addrs::MACON2, 0x40b2),
+    (addrs::MABBIPG, 0x0012),
+    (addrs::MAIPG, 0x0c12),
+    (addrs::MAMXFL, 0x05ee),
+    (addrs::ECON2, 0xcb00),
+];
+
+/// PHY register reset values
+/// See: PHY Registers, ENC424J600 Data Sheet
+const PHY_RESET: &[(u8, u16)] = &[
+    (phy::addrs::PHCON1, 0x1000),
+    (phy::addrs::PHANA, 0x05e1),
+];
+
+/// Abilities advertised by the simulated link partner
+pub const PARTNER_ABILITIES: Phanlpa = Phanlpa::from_bits_truncate(0x45e1);
+
+/// State of the SPI transaction in progress
+#[derive(Clone, Copy)]
+enum SpiState {
+    Opcode,
+    Address(u8),
+    Register(u8, u8),
+    Data(u8),
+}
+
+/// Chip state shared between the model and its SPI/NSS handles
+struct Chip {
+    sfr: [u8; SFR_SIZE],
+    phy: [u16; 32],
+    partner: Phanlpa,
+    sram: Vec<u8>,
+    mac: [u8; 6],
+    selected: bool,
+    state: SpiState,
+    clock_ready: bool,
+    link_up: bool,
+    spi_fault: bool,
+    tx_latency: u32,
+    tx_pending: Option<u32>,
+    tx_status: u16,
+    dma_latency: u32,
+    dma_pending: Option<u32>,
+    transmitted: Vec<Vec<u8>>,
+}
+
+impl Chip {
+    fn new() -> Self {
+        let mut chip = Chip {
+            sfr: [0; SFR_SIZE],
+            phy: [0; 32],
+            partner: PARTNER_ABILITIES,
+            sram: std::vec![0; SRAM_SIZE],
+            mac: FACTORY_MAC,
+            selected: false,
+            state: SpiState::Opcode,
+            clock_ready: true,
+            link_up: true,
+            spi_fault: false,
+            tx_latency: 0,
+            tx_pending: None,
+            tx_status: 0,
+            dma_latency: 0,
+            dma_pending: None,
+            transmitted: Vec::new(),
+        };
+        chip.reset();
+        chip
+    }
+
+    /// System reset, as triggered by ECON2.ETHRST
+    fn reset(&mut self) {
+        self.reset_phy();
+        self.sfr = [0; SFR_SIZE];
+        for &(addr, value) in SFR_RESET {
+            self.set_reg(addr, value);
+        }
+        self.set_reg(addrs::MAADR1, u16::from_le_bytes([self.mac[0], self.mac[1]]));
+        self.set_reg(addrs::MAADR2, u16::from_le_bytes([self.mac[2], self.mac[3]]));
+        self.set_reg(addrs::MAADR3, u16::from_le_bytes([self.mac[4], self.mac[5]]));
+        self.update_estat();
+    }
+
+    /// PHY reset, as triggered by PHCON1.PRST or a system reset
+    fn reset_phy(&mut self) {
+        self.phy = [0; 32];
+        for &(addr, value) in PHY_RESET {
+            self.phy[addr as usize] = value;
+        }
+    }
+
+    /// Speed and duplex the PHY would be running at, if the link is up
+    fn link_mode(&self) -> Option<(Speed, Duplex)> {
+        if !self.link_up {
+            return None
+        }
+        let phcon1 = Phcon1::from_bits_truncate(self.phy[phy::addrs::PHCON1 as usize]);
+        if !phcon1.contains(Phcon1::ANEN) {
+            let speed = if phcon1.contains(Phcon1::SPD100) { Speed::Mbps100 } else { Speed::Mbps10 };
+            let duplex = if phcon1.contains(Phcon1::PFULDPX) { Duplex::Full } else { Duplex::Half };
+            return Some((speed, duplex))
+        }
+        // Highest common ability wins
+        let common = self.phy[phy::addrs::PHANA as usize] & self.partner.bits();
+        let modes = [
+            (Phana::AD100FD, Speed::Mbps100, Duplex::Full),
+            (Phana::AD100, Speed::Mbps100, Duplex::Half),
+            (Phana::AD10FD, Speed::Mbps10, Duplex::Full),
+            (Phana::AD10, Speed::Mbps10, Duplex::Half),
+        ];
+        modes.iter()
+            .find(|(ability, _, _)| common & ability.bits() != 0)
+            .map(|&(_, speed, duplex)| (speed, duplex))
+    }
+
+    fn read_phy(&self, addr: u8) -> u16 {
+        let mode = self.link_mode();
+        let autoneg = self.phy[phy::addrs::PHCON1 as usize] & Phcon1::ANEN.bits() != 0;
+        match addr {
+            phy::addrs::PHSTAT1 => {
+                let mut phstat1 = Phstat1::FULL100 | Phstat1::HALF100 | Phstat1::FULL10 |
+                    Phstat1::HALF10 | Phstat1::ANABLE | Phstat1::EXTREGS;
+                if mode.is_some() { phstat1 |= Phstat1::LLSTAT }
+                if mode.is_some() && autoneg { phstat1 |= Phstat1::ANDONE }
+                phstat1.bits()
+            },
+            phy::addrs::PHANLPA if mode.is_some() && autoneg => self.partner.bits(),
+            phy::addrs::PHANLPA => 0,
+            phy::addrs::PHSTAT3 => match mode {
+                Some((Speed::Mbps10, Duplex::Half)) => 0b001 << 2,
+                Some((Speed::Mbps100, Duplex::Half)) => 0b010 << 2,
+                Some((Speed::Mbps10, Duplex::Full)) => 0b101 << 2,
+                Some((Speed::Mbps100, Duplex::Full)) => 0b110 << 2,
+                None => 0,
+            },
+            _ => self.phy[(addr & 0x1f) as usize],
+        }
+    }
+
+    fn write_phy(&mut self, addr: u8, data: u16) {
+        match addr {
+            phy::addrs::PHCON1 if data & Phcon1::PRST.bits() != 0 => self.reset_phy(),
+            // RENEG (PHCON1<9>) self-clears
+            phy::addrs::PHCON1 => self.phy[addr as usize] = data & !Phcon1::RENEG.bits(),
+            phy::addrs::PHSTAT1 | phy::addrs::PHANLPA | phy::addrs::PHSTAT3 => (),
+            _ => self.phy[(addr & 0x1f) as usize] = data,
+        }
+        self.update_estat();
+    }
+
+    fn reg(&self, addr: u8) -> u16 {
+        u16::from_le_bytes([self.sfr[addr as usize], self.sfr[addr as usize + 1]])
+    }
+
+    fn set_reg(&mut self, addr: u8, value: u16) {
+        let bytes = value.to_le_bytes();
+        self.sfr[addr as usize] = bytes[0];
+        self.sfr[addr as usize + 1] = bytes[1];
+    }
+
+    fn set_reg_bits(&mut self, addr: u8, mask: u16) {
+        self.set_reg(addr, self.reg(addr) | mask);
+    }
+
+    fn clear_reg_bits(&mut self, addr: u8, mask: u16) {
+        self.set_reg(addr, self.reg(addr) & !mask);
+    }
+
+    /// Refresh CLKRDY (ESTAT<12>), PHYDPX (ESTAT<10>) and PHYLNK (ESTAT<8>)
+    fn update_estat(&mut self) {
+        let mut estat = self.reg(addrs::ESTAT) & 0x00ff;
+        if self.clock_ready { estat |= 0x1000 }
+        match self.link_mode() {
+            Some((_, Duplex::Full)) => estat |= 0x0500,
+            Some((_, Duplex::Half)) => estat |= 0x0100,
+            None => (),
+        }
+        self.set_reg(addrs::ESTAT, estat);
+    }
+
+    fn read_sfr(&mut self, addr: u8) -> u8 {
+        // TXRTS (ECON1<1>) stays set for `tx_latency` reads of ECON1
+        if addr == addrs::ECON1 {
+            match self.tx_pending {
+                Some(0) => {
+                    self.tx_pending = None;
+                    self.transmit();
+                },
+                Some(reads) => self.tx_pending = Some(reads - 1),
+                None => (),
+            }
+            // DMAST (ECON1<5>) stays set for `dma_latency` reads of ECON1
+            match self.dma_pending {
+                Some(0) => {
+                    self.dma_pending = None;
+                    self.run_dma();
+                },
+                Some(reads) => self.dma_pending = Some(reads - 1),
+                None => (),
+            }
+        }
+        self.sfr.get(addr as usize).copied().unwrap_or(0)
+    }
+
+    fn write_sfr(&mut self, addr: u8, data: u8) {
+        match addr {
+            // ESTAT is read-only
+            a if a == addrs::ESTAT || a == addrs::ESTAT + 1 => (),
+            // ECON1<1>: TXRTS
+            a if a == addrs::ECON1 => {
+                let old = self.sfr[a as usize];
+                self.sfr[a as usize] = data;
+                if data & 0x02 != 0 && old & 0x02 == 0 {
+                    if self.tx_latency == 0 {
+                        self.transmit();
+                    } else {
+                        self.tx_pending = Some(self.tx_latency - 1);
+                    }
+                }
+                // ECON1<5>: DMAST
+                if data & 0x20 != 0 && old & 0x20 == 0 {
+                    if self.dma_latency == 0 {
+                        self.run_dma();
+                    } else {
+                        self.dma_pending = Some(self.dma_latency - 1);
+                    }
+                }
+            },
+            // ECON1<8>: PKTDEC
+            a if a == addrs::ECON1 + 1 => {
+                self.sfr[a as usize] = data & 0xfe;
+                if data & 0x01 != 0 {
+                    self.decrement_packet_count();
+                }
+            },
+            // Writing ERXST also moves ERXHEAD to the start of the ring
+            a if a == addrs::ERXST || a == addrs::ERXST + 1 => {
+                self.sfr[a as usize] = data;
+                self.set_reg(addrs::ERXHEAD, self.reg(addrs::ERXST));
+            },
+            // EIR<6>: PKTIF is read-only
+            a if a == addrs::EIR => {
+                self.sfr[a as usize] = (data & !0x40) | (self.sfr[a as usize] & 0x40);
+            },
+            // MICMD<0>: MIIRD
+            a if a == addrs::MICMD => {
+                self.sfr[a as usize] = data;
+                if data & 0x01 != 0 {
+                    let value = self.read_phy(self.sfr[addrs::MIREGADR as usize] & 0x1f);
+                    self.set_reg(addrs::MIRD, value);
+                }
+            },
+            // Writing the high byte of MIWR starts a PHY write
+            a if a == addrs::MIWR + 1 => {
+                self.sfr[a as usize] = data;
+                self.write_phy(self.sfr[addrs::MIREGADR as usize] & 0x1f, self.reg(addrs::MIWR));
+            },
+            // ECON2<4>: ETHRST
+            a if a == addrs::ECON2 => {
+                if data & 0x10 != 0 {
+                    self.reset();
+                } else {
+                    self.sfr[a as usize] = data;
+                }
+            },
+            a if (a as usize) < SFR_SIZE => self.sfr[a as usize] = data,
+            _ => (),
+        }
+    }
+
+    fn decrement_packet_count(&mut self) {
+        let estat = self.reg(addrs::ESTAT);
+        let count = (estat & 0xff).saturating_sub(1);
+        self.set_reg(addrs::ESTAT, (estat & 0xff00) | count);
+        if count == 0 {
+            // Clear PKTIF (EIR<6>)
+            self.clear_reg_bits(addrs::EIR, 0x0040);
+        }
+    }
+
+    /// Advance an RX pointer, wrapping from the end of SRAM to ERXST
+    fn next_rx_addr(&self, addr: u16) -> u16 {
+        if addr as usize >= SRAM_SIZE - 1 { self.reg(addrs::ERXST) } else { addr + 1 }
+    }
+
+    /// Advance a general-purpose pointer, wrapping from (ERXST - 1) to 0x0000
+    fn next_gp_addr(&self, addr: u16) -> u16 {
+        let next = addr + 1;
+        if next == self.reg(addrs::ERXST) || next as usize >= SRAM_SIZE { 0 } else { next }
+    }
+
+    fn spi_byte(&mut self, mosi: u8) -> u8 {
+        match self.state {
+            SpiState::Opcode => {
+                self.state = match mosi {
+                    opcodes::RCRU | opcodes::WCRU | opcodes::BFSU | opcodes::BFCU =>
+                        SpiState::Address(mosi),
+                    opcodes::RERXDATA | opcodes::WEGPDATA => SpiState::Data(mosi),
+                    _ => panic!("unsupported opcode {:#04x}", mosi),
+                };
+                0
+            },
+            SpiState::Address(opcode) => {
+                self.state = SpiState::Register(opcode, mosi);
+                0
+            },
+            SpiState::Register(opcode, addr) => {
+                // All unbanked register opcodes auto-increment the address
+                self.state = SpiState::Register(opcode, addr.wrapping_add(1));
+                match opcode {
+                    opcodes::RCRU => return self.read_sfr(addr),
+                    opcodes::WCRU => self.write_sfr(addr, mosi),
+                    opcodes::BFSU => self.write_sfr(addr, self.sfr[addr as usize] | mosi),
+                    _ => self.write_sfr(addr, self.sfr[addr as usize] & !mosi),
+                }
+                0
+            },
+            SpiState::Data(opcodes::RERXDATA) => {
+                let ptr = self.reg(addrs::ERXRDPT);
+                let miso = self.sram[ptr as usize];
+                self.set_reg(addrs::ERXRDPT, self.next_rx_addr(ptr));
+                miso
+            },
+            SpiState::Data(_) => {
+                let ptr = self.reg(addrs::EGPWRPT);
+                self.sram[ptr as usize] = mosi;
+                self.set_reg(addrs::EGPWRPT, self.next_gp_addr(ptr));
+                0
+            },
+        }
+    }
+
+    /// Send the frame at ETXST/ETXLEN and raise TXIF (EIR<3>),
+    /// or raise TXABTIF (EIR<2>) if `tx_status` reports an abort
+    fn transmit(&mut self) {
+        let start = self.reg(addrs::ETXST) as usize;
+        let len = self.reg(addrs::ETXLEN) as usize;
+        let aborted = self.tx_status & (etxstat::LATECOL | etxstat::MAXCOL | etxstat::EXDEFER) != 0;
+        if aborted {
+            self.set_reg(addrs::ETXWIRE, 0);
+            self.set_reg_bits(addrs::EIR, 0x0004);
+        } else {
+            let frame = (0..len).map(|i| self.sram[(start + i) % SRAM_SIZE]).collect();
+            self.transmitted.push(frame);
+            // Padded to 60 bytes, plus the FCS
+            self.set_reg(addrs::ETXWIRE, len.max(60) as u16 + 4);
+            self.set_reg_bits(addrs::EIR, 0x0008);
+        }
+        self.set_reg(addrs::ETXSTAT, self.tx_status);
+        self.clear_reg_bits(addrs::ECON1, 0x0002);
+    }
+
+    /// Run the DMA copy and/or checksum selected in ECON1 over EDMAST/EDMALEN,
+    /// then clear DMAST (ECON1<5>) and raise DMAIF (EIR<5>)
+    /// Pointers wrap from the end of SRAM to ERXST like the RX pointers.
+    fn run_dma(&mut self) {
+        let econ1 = self.reg(addrs::ECON1);
+        let mut src = self.reg(addrs::EDMAST);
+        let mut dst = self.reg(addrs::EDMADST);
+        // ECON1<3>: DMACSSD seeds the sum with the complement of EDMACS
+        let mut sum = if econ1 & 0x0008 != 0 { !self.reg(addrs::EDMACS) as u32 } else { 0 };
+        for i in 0..self.reg(addrs::EDMALEN) {
+            let byte = self.sram[src as usize];
+            // Bytes at even offsets are the high byte of each big-endian word
+            sum += if i & 1 == 0 { (byte as u32) << 8 } else { byte as u32 };
+            // ECON1<4>: DMACPY
+            if econ1 & 0x0010 != 0 {
+                self.sram[dst as usize] = byte;
+                dst = self.next_rx_addr(dst);
+            }
+            src = self.next_rx_addr(src);
+        }
+        // ECON1<2>: DMANOCS
+        if econ1 & 0x0004 == 0 {
+            while sum >> 16 != 0 {
+                sum = (sum & 0xffff) + (sum >> 16);
+            }
+            self.set_reg(addrs::EDMACS, !(sum as u16));
+        }
+        self.clear_reg_bits(addrs::ECON1, 0x0020);
+        self.set_reg_bits(addrs::EIR, 0x0020);
+    }
+
+    /// Decide whether the frame passes ERXFCON
+    /// Returns the RSV filter match bits of an accepted frame, or None if it is rejected
+    /// See: Section 10, ENC424J600 Data Sheet
+    fn accepts(&self, frame: &[u8], crc_ok: bool) -> Option<u64> {
+        let erxfcon = self.reg(addrs::ERXFCON);
+        let enabled = |bit: u16| erxfcon & bit != 0;
+        let runt = frame.len() + 4 < 64;
+        // CRCEEN and RUNTEEN only accept bad frames
+        if enabled(erxfcon::CRCEEN) { return if crc_ok { None } else { Some(0) } }
+        if enabled(erxfcon::RUNTEEN) { return if runt { Some(rsv::RUNT_MATCH) } else { None } }
+        // CRCEN and RUNTEN reject bad frames
+        if enabled(erxfcon::CRCEN) && !crc_ok { return None }
+        if enabled(erxfcon::RUNTEN) && runt { return None }
+        let dest = &frame[..6];
+        let broadcast = dest == [0xff; 6];
+        let multicast = !broadcast && dest[0] & 0x01 != 0;
+        let to_me = dest == self.mac_address();
+        let not_me = !broadcast && !multicast && !to_me;
+        let hash_bit = hash_table_index(dest);
+        let eht = self.reg(addrs::EHT1 + 2 * (hash_bit / 16));
+        let hash_hit = eht & (1 << (hash_bit % 16)) != 0;
+        let pattern_hit = match (erxfcon & erxfcon::PMEN_MASK) >> erxfcon::PMEN_SHIFT {
+            0b0000 => false,
+            mode => self.pattern_checksum_matches(frame) && match mode {
+                0b0001 => true,
+                0b0010 => to_me,
+                0b0011 => !to_me,
+                0b0100 => multicast,
+                0b0101 => !multicast,
+                0b0110 => broadcast,
+                0b0111 => !broadcast,
+                0b1000 => hash_hit,
+                0b1001 => self.is_magic_packet(frame),
+                _ => false,
+            },
+        };
+        let matches = [
+            (pattern_hit, rsv::PATTERN_MATCH),
+            (enabled(erxfcon::MPEN) && self.is_magic_packet(frame), rsv::MAGIC_PACKET_MATCH),
+            (hash_hit && enabled(erxfcon::HTEN), rsv::HASH_MATCH),
+            (to_me && enabled(erxfcon::UCEN), rsv::UNICAST_MATCH),
+            (not_me && enabled(erxfcon::NOTMEEN), rsv::NOT_ME_MATCH),
+            (broadcast && enabled(erxfcon::BCEN), rsv::BROADCAST_MATCH),
+            (multicast && enabled(erxfcon::MCEN), rsv::MULTICAST_MATCH),
+        ].iter()
+            .filter(|(hit, _)| *hit)
+            .fold(0, |bits, (_, bit)| bits | bit);
+        if matches != 0 { Some(matches) } else { None }
+    }
+
+    /// Whether the payload holds 6 bytes of 0xff followed by 16 copies of the station address
+    fn is_magic_packet(&self, frame: &[u8]) -> bool {
+        let mut magic = [0xff_u8; 6 * 17];
+        let mac = self.mac_address();
+        for chunk in magic[6..].chunks_mut(6) {
+            chunk.copy_from_slice(&mac);
+        }
+        frame.len() >= 14 && frame[14..].windows(magic.len()).any(|window| window == &magic[..])
+    }
+
+    /// Compare the checksum of the EPMO/EPMM window with EPMCS, inverted by NOTPM
+    fn pattern_checksum_matches(&self, frame: &[u8]) -> bool {
+        let offset = self.reg(addrs::EPMO) as usize;
+        let mask = (0..4).fold(0_u64, |mask, i| {
+            mask | (self.reg(addrs::EPMM1 + 2 * i) as u64) << (16 * i)
+        });
+        let window_end = (offset + PATTERN_WINDOW_LENGTH).min(frame.len());
+        let window = frame.get(offset..window_end).unwrap_or(&[]);
+        // The whole masked window must be present in the frame
+        if mask >> window.len() != 0 && window.len() < PATTERN_WINDOW_LENGTH {
+            return false
+        }
+        let matches = pattern_match_checksum(window, mask) == self.reg(addrs::EPMCS);
+        matches != (self.reg(addrs::ERXFCON) & erxfcon::NOTPM != 0)
+    }
+
+    fn mac_address(&self) -> [u8; 6] {
+        let mut mac = [0; 6];
+        mac[0..2].copy_from_slice(&self.reg(addrs::MAADR1).to_le_bytes());
+        mac[2..4].copy_from_slice(&self.reg(addrs::MAADR2).to_le_bytes());
+        mac[4..6].copy_from_slice(&self.reg(addrs::MAADR3).to_le_bytes());
+        mac
+    }
+
+    /// Free bytes between ERXHEAD and ERXTAIL in the RX ring
+    fn rx_free_space(&self) -> usize {
+        let start = self.reg(addrs::ERXST) as usize;
+        let head = self.reg(addrs::ERXHEAD) as usize;
+        let tail = self.reg(addrs::ERXTAIL) as usize;
+        if tail >= head {
+            tail - head
+        } else {
+            (SRAM_SIZE - head) + (tail - start)
+        }
+    }
+
+    /// Write a frame (with FCS) into the RX ring, as the receive engine would
+    fn receive(&mut self, frame: &[u8], fcs: u32) -> bool {
+        // RXEN (ECON1<0>) must be set
+        if self.reg(addrs::ECON1) & 0x0001 == 0 || frame.len() < 6 {
+            return false
+        }
+        let crc_ok = fcs == crc32(frame);
+        let matches = match self.accepts(frame, crc_ok) {
+            Some(matches) => matches,
+            None => return false,
+        };
+        let byte_count = frame.len() + 4;
+        let stored = 2 + crate::rx::RSV_LENGTH + byte_count;
+        let padded = stored + (stored & 1);
+        if padded >= self.rx_free_space() {
+            // Set RXABTIF (EIR<1>)
+            self.set_reg_bits(addrs::EIR, 0x0002);
+            return false
+        }
+        let head = self.reg(addrs::ERXHEAD);
+        let mut next = head;
+        for _ in 0..padded {
+            next = self.next_rx_addr(next);
+        }
+        let mut status = byte_count as u64 | matches;
+        if crc_ok { status |= rsv::RECEIVED_OK } else { status |= rsv::CRC_ERROR }
+        if frame[..6] == [0xff; 6] {
+            status |= rsv::BROADCAST;
+        } else if frame[0] & 0x01 != 0 {
+            status |= rsv::MULTICAST;
+        }
+        if frame.len() >= 14 {
+            match u16::from_be_bytes([frame[12], frame[13]]) {
+                0x8100 => status |= rsv::VLAN,
+                0x8808 => {
+                    status |= rsv::CONTROL_FRAME;
+                    match frame.get(14..16) {
+                        Some([0x00, 0x01]) => status |= rsv::PAUSE_FRAME,
+                        _ => status |= rsv::UNKNOWN_OPCODE,
+                    }
+                },
+                len if len as usize <= 1500 && len as usize > frame.len() - 14 =>
+                    status |= rsv::LENGTH_CHECK_ERROR,
+                len if len > 1500 && len < 0x0600 => status |= rsv::LENGTH_OUT_OF_RANGE,
+                _ => (),
+            }
+        }
+        let bytes = next.to_le_bytes().iter()
+            .chain(status.to_le_bytes()[..crate::rx::RSV_LENGTH].iter())
+            .chain(frame.iter())
+            .chain(fcs.to_le_bytes().iter())
+            .copied()
+            .collect::<Vec<u8>>();
+        let mut ptr = head;
+        for byte in bytes {
+            self.sram[ptr as usize] = byte;
+            ptr = self.next_rx_addr(ptr);
+        }
+        self.set_reg(addrs::ERXHEAD, next);
+        // Increment PKTCNT (ESTAT<7:0>) and set PKTIF (EIR<6>)
+        let estat = self.reg(addrs::ESTAT);
+        self.set_reg(addrs::ESTAT, (estat & 0xff00) | ((estat + 1) & 0xff));
+        self.set_reg_bits(addrs::EIR, 0x0040);
+        true
+    }
+}
+
+/// Simulated ENC424J600
+pub struct Enc424j600 {
+    chip: Rc<RefCell<Chip>>,
+}
+
+impl Default for Enc424j600 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Enc424j600 {
+    pub fn new() -> Self {
+        Enc424j600 {
+            chip: Rc::new(RefCell::new(Chip::new())),
+        }
+    }
+
+    /// SPI handle to pass to `SpiEth::new`
+    pub fn spi(&self) -> SimSpi {
+        SimSpi { chip: self.chip.clone() }
+    }
+
+    /// Chip-select handle to pass to `SpiEth::new`
+    pub fn nss(&self) -> SimNss {
+        SimNss { chip: self.chip.clone() }
+    }
+
+    /// Read a 16-bit SFR without going through SPI
+    pub fn read_reg(&self, addr: u8) -> u16 {
+        self.chip.borrow().reg(addr)
+    }
+
+    /// Write a 16-bit SFR without going through SPI or triggering side effects
+    pub fn write_reg(&self, addr: u8, value: u16) {
+        self.chip.borrow_mut().set_reg(addr, value);
+    }
+
+    /// Copy `len` bytes of SRAM starting at `addr`
+    pub fn read_sram(&self, addr: u16, len: usize) -> Vec<u8> {
+        let chip = self.chip.borrow();
+        (0..len).map(|i| chip.sram[(addr as usize + i) % SRAM_SIZE]).collect()
+    }
+
+    /// Write SRAM directly, without going through SPI
+    pub fn write_sram(&self, addr: u16, data: &[u8]) {
+        let mut chip = self.chip.borrow_mut();
+        for (i, byte) in data.iter().enumerate() {
+            chip.sram[(addr as usize + i) % SRAM_SIZE] = *byte;
+        }
+    }
+
+    /// Simulate the oscillator start-up: CLKRDY (ESTAT<12>) reads as `ready`
+    pub fn set_clock_ready(&self, ready: bool) {
+        let mut chip = self.chip.borrow_mut();
+        chip.clock_ready = ready;
+        chip.update_estat();
+    }
+
+    /// Simulate plugging or unplugging the cable
+    pub fn set_link_up(&self, up: bool) {
+        let mut chip = self.chip.borrow_mut();
+        if chip.link_up != up {
+            // Set LINKIF (EIR<11>)
+            chip.set_reg_bits(addrs::EIR, 0x0800);
+        }
+        chip.link_up = up;
+        chip.update_estat();
+    }
+
+    /// Keep TXRTS (ECON1<1>) set for `reads` reads of ECON1 after each transmission starts
+    /// Use `u32::MAX` for a transmission that never completes
+    pub fn set_tx_latency(&self, reads: u32) {
+        self.chip.borrow_mut().tx_latency = reads;
+    }
+
+    /// Set the ETXSTAT value reported by subsequent transmissions
+    /// LATECOL, MAXCOL or EXDEFER abort the transmission and raise TXABTIF (EIR<2>).
+    pub fn set_tx_status(&self, etxstat: u16) {
+        self.chip.borrow_mut().tx_status = etxstat;
+    }
+
+    /// Keep DMAST (ECON1<5>) set for `reads` reads of ECON1 after each DMA operation starts
+    pub fn set_dma_latency(&self, reads: u32) {
+        self.chip.borrow_mut().dma_latency = reads;
+    }
+
+    /// Read a PHY register without going through MII management
+    pub fn read_phy_reg(&self, addr: u8) -> u16 {
+        self.chip.borrow().read_phy(addr)
+    }
+
+    /// Set the abilities advertised by the link partner
+    pub fn set_partner_abilities(&self, abilities: Phanlpa) {
+        let mut chip = self.chip.borrow_mut();
+        chip.partner = abilities;
+        chip.update_estat();
+    }
+
+    /// Whether INTn is asserted: INTIE (EIE<15>) is set and an enabled flag is set in EIR
+    pub fn interrupt_asserted(&self) -> bool {
+        let chip = self.chip.borrow();
+        let eie = chip.reg(addrs::EIE);
+        eie & 0x8000 != 0 && chip.reg(addrs::EIR) & eie & 0x7fff != 0
+    }
+
+    /// Make every SPI transfer fail with `SimError` while `fault` is set
+    pub fn set_spi_fault(&self, fault: bool) {
+        self.chip.borrow_mut().spi_fault = fault;
+    }
+
+    /// Receive a frame from the wire, appending a valid FCS
+    /// Returns false if the frame was filtered out or did not fit in the RX ring
+    pub fn receive(&self, frame: &[u8]) -> bool {
+        self.chip.borrow_mut().receive(frame, crc32(frame))
+    }
+
+    /// Receive a frame from the wire with an explicit (possibly wrong) FCS
+    pub fn receive_with_fcs(&self, frame: &[u8], fcs: u32) -> bool {
+        self.chip.borrow_mut().receive(frame, fcs)
+    }
+
+    /// Take all frames sent on the wire so far
+    pub fn take_transmitted(&self) -> Vec<Vec<u8>> {
+        core::mem::take(&mut self.chip.borrow_mut().transmitted)
+    }
+}
+
+/// SPI error injected by `Enc424j600::set_spi_fault`
+#[derive(Debug, PartialEq)]
+pub struct SimError;
+
+/// SPI handle to a simulated ENC424J600
+pub struct SimSpi {
+    chip: Rc<RefCell<Chip>>,
+}
+
+impl Transfer<u8> for SimSpi {
+    type Error = SimError;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
+        let mut chip = self.chip.borrow_mut();
+        assert!(chip.selected, "SPI transfer with NSS deasserted");
+        if chip.spi_fault {
+            return Err(SimError)
+        }
+        for word in words.iter_mut() {
+            *word = chip.spi_byte(*word);
+        }
+        Ok(words)
+    }
+}
+
+/// Chip-select (NSS) handle to a simulated ENC424J600
+pub struct SimNss {
+    chip: Rc<RefCell<Chip>>,
+}
+
+impl OutputPin for SimNss {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        let mut chip = self.chip.borrow_mut();
+        chip.selected = true;
+        chip.state = SpiState::Opcode;
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.chip.borrow_mut().selected = false;
+        Ok(())
+    }
+}
+
+/// Delay provider for the model, which needs no real waiting
+pub struct SimDelay;
+
+impl DelayUs<u16> for SimDelay {
+    fn delay_us(&mut self, _us: u16) {}
+}
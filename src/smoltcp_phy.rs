@@ -1,54 +1,123 @@
 use crate::{
-    EthController, tx, RAW_FRAME_LENGTH_MAX
+    EthController, MacAddress, phy, tx, RAW_FRAME_LENGTH_MAX
 };
-use core::intrinsics::transmute;
+use core::mem::transmute;
 use smoltcp::{
-    phy::{Device, DeviceCapabilities, RxToken, TxToken},
+    phy::{Checksum, ChecksumCapabilities, Device, DeviceCapabilities, RxToken, TxToken},
     time::Instant,
+    wire::IpAddress,
     Error
 };
 
-pub struct SmoltcpDevice<'c> {
-    eth_controller: &'c mut dyn EthController<'c>,
+pub struct SmoltcpDevice<'c, E> {
+    eth_controller: &'c mut dyn EthController<'c, Error = E>,
     rx_packet_buf: [u8; RAW_FRAME_LENGTH_MAX],
     tx_packet_buf: [u8; RAW_FRAME_LENGTH_MAX]
 }
 
-impl<'c> SmoltcpDevice<'c> {
-    pub fn new(eth_controller: &'c mut dyn EthController<'c>) -> Self {
+impl<'c, E> SmoltcpDevice<'c, E> {
+    pub fn new(eth_controller: &'c mut dyn EthController<'c, Error = E>) -> Self {
         SmoltcpDevice {
             eth_controller,
             rx_packet_buf: [0; RAW_FRAME_LENGTH_MAX],
             tx_packet_buf: [0; RAW_FRAME_LENGTH_MAX]
         }
     }
+
+    /// Complete the frame on the wire and start the next staged one, see
+    /// `EthController::service_tx_queue`; also done on every `receive` and `transmit`
+    pub fn poll_tx(&mut self) -> Result<Option<tx::TxStatus>, E> {
+        self.eth_controller.service_tx_queue()
+    }
+
+    /// Get the link status of the controller, e.g. to restart DHCP when the link returns
+    pub fn link_status(&mut self) -> Result<phy::LinkStatus, E> {
+        self.eth_controller.link_status()
+    }
+
+    /// Check whether the link went up or down since the last call
+    pub fn link_changed(&mut self) -> Result<bool, E> {
+        self.eth_controller.link_changed()
+    }
+
+    /// Accept frames to the MAC address of a multicast group in the hash table filter
+    /// Call this alongside joining the group on the interface, e.g. with
+    /// `iface.device_mut().join_multicast_group(addr)`.
+    /// Returns false if `addr` is not a multicast address.
+    pub fn join_multicast_group(&mut self, addr: IpAddress) -> Result<bool, E> {
+        match multicast_mac(addr) {
+            Some(mac) => self.eth_controller.add_multicast(mac).map(|_| true),
+            None => Ok(false)
+        }
+    }
+
+    /// Drop a multicast group joined with `join_multicast_group`
+    pub fn leave_multicast_group(&mut self, addr: IpAddress) -> Result<bool, E> {
+        match multicast_mac(addr) {
+            Some(mac) => self.eth_controller.remove_multicast(mac).map(|_| true),
+            None => Ok(false)
+        }
+    }
 }
 
-impl<'a, 'c> Device<'a> for SmoltcpDevice<'c> {
+/// Map a multicast IP address to its Ethernet MAC address (RFC 1112, RFC 2464)
+fn multicast_mac(addr: IpAddress) -> Option<MacAddress> {
+    match addr {
+        IpAddress::Ipv4(ip) if ip.is_multicast() => {
+            let b = ip.as_bytes();
+            Some(MacAddress::new([0x01, 0x00, 0x5e, b[1] & 0x7f, b[2], b[3]]))
+        },
+        IpAddress::Ipv6(ip) if ip.is_multicast() => {
+            let b = ip.as_bytes();
+            Some(MacAddress::new([0x33, 0x33, b[12], b[13], b[14], b[15]]))
+        },
+        _ => None
+    }
+}
+
+impl<'a, 'c, E: 'a> Device<'a> for SmoltcpDevice<'c, E> {
     type RxToken = EthRxToken<'a>;
-    type TxToken = EthTxToken<'a>;
+    type TxToken = EthTxToken<'a, E>;
 
     fn capabilities(&self) -> DeviceCapabilities {
         let mut caps = DeviceCapabilities::default();
         caps.max_transmission_unit = RAW_FRAME_LENGTH_MAX;
+        // Checksums of sent frames are computed by the DMA engine, see `EthTxToken`, and
+        // those of received frames verified by it if enabled on the controller,
+        // see `EthController::set_rx_checksum_offload`
+        let mode = if self.eth_controller.is_rx_checksum_offload() {
+            Checksum::None
+        } else {
+            Checksum::Rx
+        };
+        let mut checksum = ChecksumCapabilities::default();
+        checksum.ipv4 = mode;
+        checksum.tcp = mode;
+        checksum.udp = mode;
+        checksum.icmpv4 = mode;
+        checksum.icmpv6 = mode;
+        caps.checksum = checksum;
         caps
     }
 
     fn receive(&'a mut self) -> Option<(Self::RxToken, Self::TxToken)> {
         // Extend self lifetime from 'c to 'a for tokens' access to EthController
         let self_trans = unsafe {
-            transmute::<&mut SmoltcpDevice<'c>, &mut SmoltcpDevice<'a>>(&mut *self)
+            transmute::<&mut SmoltcpDevice<'c, E>, &mut SmoltcpDevice<'a, E>>(&mut *self)
         };
         // Make self_a point to *self that has a lifetime of 'a (extended)
-        let self_a = self_trans as *mut SmoltcpDevice<'a>;
+        let self_a = self_trans as *mut SmoltcpDevice<'a, E>;
+        // Keep frames staged behind a busy MAC moving while the interface polls;
+        // an aborted frame is simply lost, as if it had been dropped on the wire
+        let _ = self_trans.eth_controller.service_tx_queue();
         match self_trans.eth_controller.receive_next(false) {
             Ok(rx_packet) => {
-                // Write received packet to RX packet buffer
-                rx_packet.write_frame_to(&mut self.rx_packet_buf);
+                // Write received packet without the FCS to RX packet buffer
+                rx_packet.write_payload_to(&mut self.rx_packet_buf);
                 // Construct a RxToken
                 let rx_token = EthRxToken {
                     buf: &mut self.rx_packet_buf,
-                    len: rx_packet.get_frame_length()
+                    len: rx_packet.get_payload_length()
                 };
                 // Construct a blank TxToken
                 let tx_token = EthTxToken {
@@ -64,10 +133,11 @@ impl<'a, 'c> Device<'a> for SmoltcpDevice<'c> {
     fn transmit(&'a mut self) -> Option<Self::TxToken> {
         // Extend self lifetime from 'c to 'a for TxToken's access to EthController
         let self_trans = unsafe {
-            transmute::<&mut SmoltcpDevice<'c>, &mut SmoltcpDevice<'a>>(&mut *self)
+            transmute::<&mut SmoltcpDevice<'c, E>, &mut SmoltcpDevice<'a, E>>(&mut *self)
         };
         // Make self_a point to *self that has a lifetime of 'a (extended)
-        let self_a = self_trans as *mut SmoltcpDevice<'a>;
+        let self_a = self_trans as *mut SmoltcpDevice<'a, E>;
+        let _ = self_trans.eth_controller.service_tx_queue();
         // Construct a blank TxToken
         let tx_token = EthTxToken {
             buf: &mut self.tx_packet_buf,
@@ -91,12 +161,12 @@ impl<'a> RxToken for EthRxToken<'a> {
     }
 }
 
-pub struct EthTxToken<'a> {
+pub struct EthTxToken<'a, E> {
     buf: &'a mut [u8],
-    dev: *mut SmoltcpDevice<'a>
+    dev: *mut SmoltcpDevice<'a, E>
 }
 
-impl<'a> TxToken for EthTxToken<'a> {
+impl<'a, E> TxToken for EthTxToken<'a, E> {
     fn consume<R, F>(self, _timestamp: Instant, len: usize, f: F) -> Result<R, Error>
     where
         F: FnOnce(&mut [u8]) -> Result<R, Error>,
@@ -105,14 +175,178 @@ impl<'a> TxToken for EthTxToken<'a> {
         // Construct a TxPacket
         let mut tx_packet = tx::TxPacket::new();
         // Update frame length and write frame bytes
-        tx_packet.update_frame(&mut self.buf[..len], len);
-        // Send the packet as raw
+        tx_packet.update_frame(&self.buf[..len], len);
+        // Let the controller fill in the checksums skipped by smoltcp
+        tx_packet.set_checksum_offload(true);
+        // Stage the packet in the TX queue without waiting for the wire
         let eth_controller = unsafe {
             &mut (*self.dev).eth_controller
         };
-        match eth_controller.send_raw_packet(&tx_packet) {
+        // Reap a completed frame first to make room
+        let _ = eth_controller.service_tx_queue();
+        match eth_controller.queue_packet(&tx_packet) {
             Ok(_) => { result },
             Err(_) => Err(Error::Exhausted)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sim::{Enc424j600, SimDelay, SimNss, SimSpi}, SpiEth};
+    use smoltcp::wire::{Icmpv4Packet, Ipv4Address, Ipv4Packet, Ipv6Address, TcpPacket};
+
+    fn setup() -> (Enc424j600, SpiEth<SimSpi, SimNss>) {
+        let chip = Enc424j600::new();
+        let mut eth = SpiEth::new(chip.spi(), chip.nss()).unwrap();
+        assert!(eth.init_dev(&mut SimDelay).is_ok());
+        assert!(eth.init_rxbuf().is_ok());
+        assert!(eth.init_txbuf().is_ok());
+        (chip, eth)
+    }
+
+    #[test]
+    fn multicast_mac_mapping() {
+        assert_eq!(multicast_mac(IpAddress::Ipv4(Ipv4Address::new(224, 0, 0, 251))),
+            Some(MacAddress::new([0x01, 0x00, 0x5e, 0x00, 0x00, 0xfb])));
+        assert_eq!(multicast_mac(IpAddress::Ipv4(Ipv4Address::new(239, 255, 1, 2))),
+            Some(MacAddress::new([0x01, 0x00, 0x5e, 0x7f, 0x01, 0x02])));
+        let solicited = Ipv6Address::new(0xff02, 0, 0, 0, 0, 1, 0xff12, 0x3456);
+        assert_eq!(multicast_mac(IpAddress::Ipv6(solicited)),
+            Some(MacAddress::new([0x33, 0x33, 0xff, 0x12, 0x34, 0x56])));
+        assert_eq!(multicast_mac(IpAddress::Ipv4(Ipv4Address::new(192, 168, 1, 1))), None);
+    }
+
+    #[test]
+    fn join_multicast_group_programs_hash_table() {
+        let (chip, mut eth) = setup();
+        let mut device = SmoltcpDevice::new(&mut eth);
+        let mdns = IpAddress::Ipv4(Ipv4Address::new(224, 0, 0, 251));
+        let mut frame = [0; 60];
+        frame[..6].copy_from_slice(&[0x01, 0x00, 0x5e, 0x00, 0x00, 0xfb]);
+        assert!(!chip.receive(&frame));
+        assert_eq!(device.join_multicast_group(mdns).ok(), Some(true));
+        assert!(chip.receive(&frame));
+        assert_eq!(device.leave_multicast_group(mdns).ok(), Some(true));
+        assert!(!chip.receive(&frame));
+    }
+
+    #[test]
+    fn receive_strips_fcs() {
+        let (chip, mut eth) = setup();
+        let mut device = SmoltcpDevice::new(&mut eth);
+        let mut frame = [0x5a; 60];
+        frame[..6].copy_from_slice(&[0xff; 6]);
+        assert!(chip.receive(&frame));
+        let (rx_token, _) = device.receive().unwrap();
+        let received = rx_token.consume(Instant::from_millis(0), |buf| {
+            assert_eq!(buf, &frame[..]);
+            Ok(buf.len())
+        });
+        assert_eq!(received, Ok(60));
+    }
+
+    #[test]
+    fn transmit_queues_frames() {
+        let (chip, mut eth) = setup();
+        chip.set_tx_latency(10);
+        let mut device = SmoltcpDevice::new(&mut eth);
+        for i in 0..3 {
+            let tx_token = device.transmit().unwrap();
+            let sent = tx_token.consume(Instant::from_millis(0), 60, |buf| {
+                buf.copy_from_slice(&[i; 60]);
+                Ok(())
+            });
+            assert_eq!(sent, Ok(()));
+        }
+        // Consuming a token does not wait for the frame to leave
+        let mut sent = chip.take_transmitted();
+        assert!(sent.len() < 3);
+        // Polling the device for received frames moves the rest of the queue
+        for _ in 0..100 {
+            assert!(device.receive().is_none());
+        }
+        assert!(matches!(device.poll_tx(), Ok(None)));
+        sent.extend(chip.take_transmitted());
+        assert_eq!(sent.len(), 3);
+        assert!(sent.iter().enumerate().all(|(i, frame)| frame[..] == [i as u8; 60][..]));
+    }
+
+    #[test]
+    fn transmit_offloads_checksums() {
+        let (chip, mut eth) = setup();
+        let mut device = SmoltcpDevice::new(&mut eth);
+        let caps = device.capabilities();
+        assert!(!caps.checksum.ipv4.tx() && !caps.checksum.tcp.tx() && !caps.checksum.udp.tx());
+        assert!(!caps.checksum.icmpv4.tx() && !caps.checksum.icmpv6.tx());
+        // IPv4 ICMP echo request, with both checksums left at zero by smoltcp
+        let mut icmp = [0; 60];
+        icmp[12..14].copy_from_slice(&[0x08, 0x00]);
+        icmp[14..34].copy_from_slice(&[0x45, 0x00, 0x00, 0x24, 0x12, 0x34, 0x00, 0x00, 0x40, 0x01,
+                                       0x00, 0x00, 0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02]);
+        icmp[34..42].copy_from_slice(&[0x08, 0x00, 0x00, 0x00, 0xbe, 0xef, 0x00, 0x01]);
+        icmp[42..50].copy_from_slice(b"abcdefgh");
+        // IPv6 TCP segment with an odd length payload
+        let src = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+        let dst = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+        let mut tcp = [0; 14 + 40 + 23];
+        tcp[12..14].copy_from_slice(&[0x86, 0xdd]);
+        tcp[14..20].copy_from_slice(&[0x60, 0x00, 0x00, 0x00, 0x00, 0x17]);
+        tcp[20..22].copy_from_slice(&[6, 64]);
+        tcp[22..38].copy_from_slice(src.as_bytes());
+        tcp[38..54].copy_from_slice(dst.as_bytes());
+        tcp[54..58].copy_from_slice(&[0x30, 0x39, 0x00, 0x50]);
+        tcp[66] = 0x50;
+        tcp[74..77].copy_from_slice(b"xyz");
+        for frame in [&icmp[..], &tcp[..]].iter() {
+            let tx_token = device.transmit().unwrap();
+            let sent = tx_token.consume(Instant::from_millis(0), frame.len(), |buf| {
+                buf.copy_from_slice(frame);
+                Ok(())
+            });
+            assert_eq!(sent, Ok(()));
+        }
+        let sent = chip.take_transmitted();
+        assert_eq!(sent.len(), 2);
+        assert!(Ipv4Packet::new_checked(&sent[0][14..]).unwrap().verify_checksum());
+        assert!(Icmpv4Packet::new_checked(&sent[0][34..50]).unwrap().verify_checksum());
+        assert!(TcpPacket::new_checked(&sent[1][54..]).unwrap()
+            .verify_checksum(&src.into(), &dst.into()));
+    }
+
+    #[test]
+    fn receive_offloads_checksums() {
+        let (chip, mut eth) = setup();
+        // smoltcp verifies received frames unless the controller does
+        let caps = SmoltcpDevice::new(&mut eth).capabilities();
+        assert!(caps.checksum.ipv4.rx() && caps.checksum.tcp.rx() && caps.checksum.udp.rx());
+        eth.set_rx_checksum_offload(true);
+        let mut device = SmoltcpDevice::new(&mut eth);
+        let caps = device.capabilities();
+        assert!(!caps.checksum.ipv4.rx() && !caps.checksum.tcp.rx() && !caps.checksum.udp.rx());
+        assert!(!caps.checksum.icmpv4.rx() && !caps.checksum.icmpv6.rx());
+        // Broadcast IPv4 ICMP echo request with valid checksums
+        let mut icmp = [0; 60];
+        icmp[..6].copy_from_slice(&[0xff; 6]);
+        icmp[12..14].copy_from_slice(&[0x08, 0x00]);
+        icmp[14..34].copy_from_slice(&[0x45, 0x00, 0x00, 0x24, 0x12, 0x34, 0x00, 0x00, 0x40, 0x01,
+                                       0x00, 0x00, 0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0xff]);
+        icmp[34..42].copy_from_slice(&[0x08, 0x00, 0x00, 0x00, 0xbe, 0xef, 0x00, 0x01]);
+        icmp[42..50].copy_from_slice(b"abcdefgh");
+        Ipv4Packet::new_unchecked(&mut icmp[14..]).fill_checksum();
+        Icmpv4Packet::new_unchecked(&mut icmp[34..50]).fill_checksum();
+        let mut corrupted = icmp;
+        corrupted[45] ^= 0x01;
+        assert!(chip.receive(&corrupted));
+        assert!(chip.receive(&icmp));
+        let (rx_token, _) = device.receive().unwrap();
+        let received = rx_token.consume(Instant::from_millis(0), |buf| {
+            assert_eq!(buf, &icmp[..]);
+            Ok(buf.len())
+        });
+        assert_eq!(received, Ok(60));
+        assert!(device.receive().is_none());
+        assert_eq!(eth.get_rx_error_counters().checksum_error, 1);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::{EthControllerError, SpiEthError};
 use embedded_hal::{
     blocking::spi::Transfer,
     digital::v2::OutputPin,
@@ -18,6 +19,8 @@ pub mod opcodes {
     /// SPI Opcodes
     pub const RCRU: u8 = 0b0010_0000;
     pub const WCRU: u8 = 0b0010_0010;
+    pub const BFSU: u8 = 0b0010_0100;
+    pub const BFCU: u8 = 0b0010_0110;
     pub const RERXDATA: u8 = 0b0010_1100;   // 8-bit opcode followed by data
     pub const WEGPDATA: u8 = 0b0010_1010;   // 8-bit opcode followed by data
 }
@@ -27,10 +30,21 @@ pub mod addrs {
     /// Note: PSP interface use different address mapping
     // SPI Init Reset Registers
     pub const EUDAST: u8 = 0x16;        // 16-bit data
+    pub const EUDAND: u8 = 0x18;        // 16-bit data
     pub const ESTAT: u8 = 0x1a;         // 16-bit data
     pub const ECON2: u8 = 0x6e;         // 16-bit data
     //
     pub const ERXFCON: u8 = 0x34;       // 16-bit data
+    pub const EHT1: u8 = 0x20;          // 16-bit data
+    pub const EHT2: u8 = 0x22;          // 16-bit data
+    pub const EHT3: u8 = 0x24;          // 16-bit data
+    pub const EHT4: u8 = 0x26;          // 16-bit data
+    pub const EPMM1: u8 = 0x28;         // 16-bit data
+    pub const EPMM2: u8 = 0x2a;         // 16-bit data
+    pub const EPMM3: u8 = 0x2c;         // 16-bit data
+    pub const EPMM4: u8 = 0x2e;         // 16-bit data
+    pub const EPMCS: u8 = 0x30;         // 16-bit data
+    pub const EPMO: u8 = 0x32;          // 16-bit data
     //
     pub const MAADR3: u8 = 0x60;        // 16-bit data
     pub const MAADR2: u8 = 0x62;        // 16-bit data
@@ -39,6 +53,7 @@ pub mod addrs {
     pub const ERXRDPT: u8 = 0x8a;       // 16-bit data
     pub const ERXST: u8 = 0x04;         // 16-bit data
     pub const ERXTAIL: u8 = 0x06;       // 16-bit data
+    pub const ERXHEAD: u8 = 0x08;       // 16-bit data
     pub const EIR: u8 = 0x1c;           // 16-bit data
     pub const ECON1: u8 = 0x1e;         // 16-bit data
     pub const MAMXFL: u8 = 0x4a;        // 16-bit data
@@ -46,7 +61,25 @@ pub mod addrs {
     pub const EGPWRPT: u8 = 0x88;       // 16-bit data
     pub const ETXST: u8 = 0x00;         // 16-bit data
     pub const ETXSTAT: u8 = 0x12;       // 16-bit data
+    pub const ETXWIRE: u8 = 0x14;       // 16-bit data
     pub const ETXLEN: u8 = 0x02;        // 16-bit data
+    // DMA Registers
+    pub const EDMAST: u8 = 0x0a;        // 16-bit data
+    pub const EDMALEN: u8 = 0x0c;       // 16-bit data
+    pub const EDMADST: u8 = 0x0e;       // 16-bit data
+    pub const EDMACS: u8 = 0x10;        // 16-bit data
+    // MAC Registers
+    pub const MACON2: u8 = 0x42;        // 16-bit data
+    pub const MABBIPG: u8 = 0x44;       // 16-bit data
+    pub const MAIPG: u8 = 0x46;         // 16-bit data
+    // Interrupt Registers
+    pub const EIE: u8 = 0x72;           // 16-bit data
+    // MII Management Registers
+    pub const MICMD: u8 = 0x52;         // 16-bit data
+    pub const MIREGADR: u8 = 0x54;      // 16-bit data
+    pub const MIWR: u8 = 0x66;          // 16-bit data
+    pub const MIRD: u8 = 0x68;          // 16-bit data
+    pub const MISTAT: u8 = 0x6a;        // 16-bit data
 }
 
 /// Struct for SPI I/O interface on ENC424J600
@@ -57,30 +90,26 @@ pub struct SpiPort<SPI: Transfer<u8>,
     nss: NSS,
 }
 
-pub enum SpiPortError {
-    TransferError
-}
+type Error<SPI, NSS> = SpiEthError<SPI, NSS>;
 
-#[allow(unused_must_use)]
 impl <SPI: Transfer<u8>,
       NSS: OutputPin> SpiPort<SPI, NSS> {
-    // TODO: return as Result()
-    pub fn new(spi: SPI, mut nss: NSS) -> Self {
-        nss.set_high();
+    pub fn new(spi: SPI, mut nss: NSS) -> Result<Self, Error<SPI, NSS>> {
+        nss.set_high().map_err(EthControllerError::Pin)?;
 
-        SpiPort {
+        Ok(SpiPort {
             spi,
             nss
-        }
+        })
     }
 
-    pub fn read_reg_8b(&mut self, addr: u8) -> Result<u8, SpiPortError> {
+    pub fn read_reg_8b(&mut self, addr: u8) -> Result<u8, Error<SPI, NSS>> {
         // Using RCRU instruction to read using unbanked (full) address
         let r_data = self.rw_addr_u8(opcodes::RCRU, addr, 0)?;
         Ok(r_data)
     }
 
-    pub fn read_reg_16b(&mut self, lo_addr: u8) -> Result<u16, SpiPortError> {
+    pub fn read_reg_16b(&mut self, lo_addr: u8) -> Result<u16, Error<SPI, NSS>> {
         let r_data_lo = self.read_reg_8b(lo_addr)?;
         let r_data_hi = self.read_reg_8b(lo_addr + 1)?;
         // Combine top and bottom 8-bit to return 16-bit
@@ -88,108 +117,100 @@ impl <SPI: Transfer<u8>,
     }
 
     // Currently requires manual slicing (buf[1..]) for the data read back
-    pub fn read_rxdat<'a>(&mut self, buf: &'a mut [u8], data_length: usize)
-                         -> Result<(), SpiPortError> {
-        let r_valid = self.r_n(buf, opcodes::RERXDATA, data_length)?;
-        Ok(r_valid)
+    pub fn read_rxdat(&mut self, buf: &mut [u8], data_length: usize)
+                     -> Result<(), Error<SPI, NSS>> {
+        self.r_n(buf, opcodes::RERXDATA, data_length)
     }
 
     // Currenly requires actual data to be stored in buf[1..] instead of buf[0..]
     // TODO: Maybe better naming?
-    pub fn write_txdat<'a>(&mut self, buf: &'a mut [u8], data_length: usize)
-                          -> Result<(), SpiPortError> {
-        let w_valid = self.w_n(buf, opcodes::WEGPDATA, data_length)?;
-        Ok(w_valid)
+    pub fn write_txdat(&mut self, buf: &mut [u8], data_length: usize)
+                      -> Result<(), Error<SPI, NSS>> {
+        self.w_n(buf, opcodes::WEGPDATA, data_length)
     }
 
-    pub fn write_reg_8b(&mut self, addr: u8, data: u8) -> Result<(), SpiPortError> {
+    pub fn write_reg_8b(&mut self, addr: u8, data: u8) -> Result<(), Error<SPI, NSS>> {
         // TODO: addr should be separated from w_data
         // Using WCRU instruction to write using unbanked (full) address
         self.rw_addr_u8(opcodes::WCRU, addr, data)?;
         Ok(())
     }
 
-    pub fn write_reg_16b(&mut self, lo_addr: u8, data: u16) -> Result<(), SpiPortError> {
+    pub fn write_reg_16b(&mut self, lo_addr: u8, data: u16) -> Result<(), Error<SPI, NSS>> {
         self.write_reg_8b(lo_addr, (data & 0xff) as u8)?;
         self.write_reg_8b(lo_addr + 1, ((data & 0xff00) >> 8) as u8)?;
         Ok(())
     }
 
+    pub fn set_bits_8b(&mut self, addr: u8, mask: u8) -> Result<(), Error<SPI, NSS>> {
+        // Using BFSU instruction to set bits atomically using unbanked (full) address
+        self.rw_addr_u8(opcodes::BFSU, addr, mask)?;
+        Ok(())
+    }
+
+    pub fn clear_bits_8b(&mut self, addr: u8, mask: u8) -> Result<(), Error<SPI, NSS>> {
+        // Using BFCU instruction to clear bits atomically using unbanked (full) address
+        self.rw_addr_u8(opcodes::BFCU, addr, mask)?;
+        Ok(())
+    }
+
+    // Asserts NSS, transfers buf, then deasserts NSS even if the transfer failed
+    // A transfer error takes precedence over an error on deasserting NSS
+    fn transfer_selected(&mut self, buf: &mut [u8], opcode: u8, addr: Option<u8>)
+                        -> Result<(), Error<SPI, NSS>> {
+        // Enable chip select
+        self.nss.set_low().map_err(EthControllerError::Pin)?;
+        // Start writing to SLAVE
+        let result = self.spi.transfer(buf)
+            .map(|_| ())
+            .map_err(|error| EthControllerError::Spi { opcode, addr, error });
+        // Disable chip select
+        let nss_result = self.nss.set_high().map_err(EthControllerError::Pin);
+        result.and(nss_result)
+    }
+
     // TODO: Generalise transfer functions
     // TODO: (Make data read/write as reference to array)
     // Currently requires 1-byte addr, read/write data is only 1-byte
     fn rw_addr_u8(&mut self, opcode: u8, addr: u8, data: u8)
-                 -> Result<u8, SpiPortError> {
-        // Enable chip select
-        self.nss.set_low();
-        // Start writing to SLAVE
+                 -> Result<u8, Error<SPI, NSS>> {
         // TODO: don't just use 3 bytes
         let mut buf: [u8; 3] = [0; 3];
         buf[0] = opcode;
         buf[1] = addr;
         buf[2] = data;
-        match self.spi.transfer(&mut buf) {
-            Ok(_) => {
-                // Disable chip select
-                self.nss.set_high();
-                Ok(buf[2])
-            },
-            // TODO: Maybe too naive?
-            Err(_) => {
-                // Disable chip select
-                self.nss.set_high();
-                Err(SpiPortError::TransferError)
-            }
-        }
+        self.transfer_selected(&mut buf, opcode, Some(addr))?;
+        Ok(buf[2])
     }
 
     // TODO: Generalise transfer functions
     // Currently does NOT accept addr, read data is N-byte long
     // Returns a reference to the data returned
     // Note: buf must be at least (data_length + 1)-byte long
-    // TODO: Check and raise error for array size < (data_length + 1)
-    fn r_n<'a>(&mut self, buf: &'a mut [u8], opcode: u8, data_length: usize)
-              -> Result<(), SpiPortError> {
-        // Enable chip select
-        self.nss.set_low();
-        // Start writing to SLAVE
-        buf[0] = opcode;
-        match self.spi.transfer(&mut buf[..data_length+1]) {
-            Ok(_) => {
-                // Disable chip select
-                self.nss.set_high();
-                Ok(())
-            },
-            // TODO: Maybe too naive?
-            Err(_) => {
-                // Disable chip select
-                self.nss.set_high();
-                Err(SpiPortError::TransferError)
-            }
+    fn r_n(&mut self, buf: &mut [u8], opcode: u8, data_length: usize)
+          -> Result<(), Error<SPI, NSS>> {
+        if buf.len() < data_length + 1 {
+            return Err(EthControllerError::InvalidBufferLength {
+                len: buf.len(),
+                required: data_length + 1
+            })
         }
+        buf[0] = opcode;
+        self.transfer_selected(&mut buf[..data_length+1], opcode, None)
     }
 
     // Note: buf[0] is currently reserved for opcode to overwrite
     // TODO: Actual data should start from buf[0], not buf[1]
-    fn w_n<'a>(&mut self, buf: &'a mut [u8], opcode: u8, data_length: usize)
-              -> Result<(), SpiPortError> {
-        // Enable chip select
-        self.nss.set_low();
-        // Start writing to SLAVE
+    fn w_n(&mut self, buf: &mut [u8], opcode: u8, data_length: usize)
+          -> Result<(), Error<SPI, NSS>> {
+        if buf.len() < data_length + 1 {
+            return Err(EthControllerError::InvalidBufferLength {
+                len: buf.len(),
+                required: data_length + 1
+            })
+        }
         buf[0] = opcode;
         // TODO: Maybe need to copy data to buf later on
-        match self.spi.transfer(&mut buf[..data_length+1]) {
-            Ok(_) => {
-                // Disable chip select
-                self.nss.set_high();
-                Ok(())
-            },
-            // TODO: Maybe too naive?
-            Err(_) => {
-                // Disable chip select
-                self.nss.set_high();
-                Err(SpiPortError::TransferError)
-            }
-        }
+        self.transfer_selected(&mut buf[..data_length+1], opcode, None)
     }
-}
\ No newline at end of file
+}
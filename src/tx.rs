@@ -1,24 +1,111 @@
-use crate::RAW_FRAME_LENGTH_MAX;
+use crate::{MacAddress, RAW_FRAME_LENGTH_MAX};
 
 /// SRAM Addresses
 pub const GPBUFST_DEFAULT: u16 = 0x0000;    // Start of General-Purpose SRAM Buffer
 pub const GPBUFEN_DEFAULT: u16 = 0x5340;    // End of General-Purpose SRAM Buffer == ERXST default
 
+/// EtherType used for Wake-on-LAN Magic Packets
+pub const ETHERTYPE_WAKE_ON_LAN: u16 = 0x0842;
+/// Minimum frame length without FCS; shorter frames are padded by the MAC
+const MIN_FRAME_LENGTH: usize = 60;
+
+/// ETXSTAT Bits
+/// See: ETXSTAT, ENC424J600 Data Sheet
+pub mod etxstat {
+    pub const LATECOL: u16 = 1 << 10;
+    pub const MAXCOL: u16 = 1 << 9;
+    pub const EXDEFER: u16 = 1 << 8;
+    pub const DEFER: u16 = 1 << 7;
+    pub const CRCBAD: u16 = 1 << 4;
+    pub const COLCNT_MASK: u16 = 0b1111;
+}
+
+/// Status of the last transmission, read from ETXSTAT and ETXWIRE
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TxStatus {
+    etxstat: u16,
+    etxwire: u16,
+}
+
+impl TxStatus {
+    pub fn from_bits(etxstat: u16, etxwire: u16) -> Self {
+        TxStatus { etxstat, etxwire }
+    }
+    pub fn bits(&self) -> u16 {
+        self.etxstat
+    }
+
+    /// Number of collisions during the transmission, COLCNT<3:0>
+    pub fn get_collision_count(&self) -> u8 {
+        (self.etxstat & etxstat::COLCNT_MASK) as u8
+    }
+    /// Whether the FCS of the transmitted frame matched the CRC generated by the MAC
+    pub fn is_crc_ok(&self) -> bool {
+        self.etxstat & etxstat::CRCBAD == 0
+    }
+    /// Whether the transmission was aborted by a collision after the collision window
+    pub fn is_late_collision(&self) -> bool {
+        self.etxstat & etxstat::LATECOL != 0
+    }
+    /// Whether the transmission was aborted after the maximum number of retries
+    pub fn is_max_collision_abort(&self) -> bool {
+        self.etxstat & etxstat::MAXCOL != 0
+    }
+    /// Whether the medium was busy when the transmission was first attempted
+    pub fn is_deferred(&self) -> bool {
+        self.etxstat & etxstat::DEFER != 0
+    }
+    /// Whether the transmission was aborted after deferring for too long
+    pub fn is_excessive_defer(&self) -> bool {
+        self.etxstat & etxstat::EXDEFER != 0
+    }
+    /// Whether the MAC gave up on the frame
+    pub fn is_aborted(&self) -> bool {
+        self.etxstat & (etxstat::LATECOL | etxstat::MAXCOL | etxstat::EXDEFER) != 0
+    }
+    /// Number of bytes put on the wire by the last attempt, including padding and FCS
+    pub fn get_wire_length(&self) -> u16 {
+        self.etxwire
+    }
+}
+
+/// Number of frames that can be staged in the TX buffer at once
+pub const TX_QUEUE_LENGTH: usize = 8;
+
 /// Struct for TX Buffer on the hardware
+/// Frames are staged back to back from `wrap_addr` up to `end_addr`, each one
+/// contiguous, and sent in the order they were pushed.
 /// TODO: Should be a singleton
 pub struct TxBuffer {
     wrap_addr: u16,
+    end_addr: u16,
     // The following two fields are controlled by firmware
     next_addr: u16,
-    tail_addr: u16
+    tail_addr: u16,
+    // Start address and length of each staged frame, oldest first from `queue_head`
+    queue: [(u16, u16); TX_QUEUE_LENGTH],
+    queue_head: usize,
+    queue_len: usize,
+    transmitting: bool
+}
+
+impl Default for TxBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TxBuffer {
     pub fn new() -> Self {
         TxBuffer {
             wrap_addr: GPBUFST_DEFAULT,
-            next_addr: GPBUFST_DEFAULT + 1,
-            tail_addr: GPBUFST_DEFAULT
+            end_addr: GPBUFEN_DEFAULT,
+            next_addr: GPBUFST_DEFAULT,
+            tail_addr: GPBUFST_DEFAULT,
+            queue: [(0, 0); TX_QUEUE_LENGTH],
+            queue_head: 0,
+            queue_len: 0,
+            transmitting: false
         }
     }
 
@@ -29,6 +116,14 @@ impl TxBuffer {
         self.wrap_addr
     }
 
+    /// End of the TX region, exclusive
+    pub fn set_end_addr(&mut self, addr: u16) {
+        self.end_addr = addr;
+    }
+    pub fn get_end_addr(& self) -> u16{
+        self.end_addr
+    }
+
     pub fn set_next_addr(&mut self, addr: u16) {
         self.next_addr = addr;
     }
@@ -42,40 +137,152 @@ impl TxBuffer {
     pub fn get_tail_addr(& self) -> u16{
         self.tail_addr
     }
+
+    /// Drop all staged frames and restart at `wrap_addr`
+    pub fn reset(&mut self) {
+        self.next_addr = self.wrap_addr;
+        self.tail_addr = self.wrap_addr;
+        self.queue_head = 0;
+        self.queue_len = 0;
+        self.transmitting = false;
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue_len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.queue_len == 0
+    }
+    pub fn is_full(&self) -> bool {
+        self.queue_len == TX_QUEUE_LENGTH
+    }
+
+    /// Start address for a frame of `len` bytes that does not overlap staged frames
+    /// or cross `end_addr`, wrapping to `wrap_addr` if needed; None if it does not fit yet
+    pub fn allocate(&self, len: u16) -> Option<u16> {
+        if self.is_full() {
+            return None
+        }
+        if self.is_empty() {
+            let fits = self.wrap_addr as u32 + len as u32 <= self.end_addr as u32;
+            return if fits { Some(self.wrap_addr) } else { None }
+        }
+        let end = self.next_addr as u32 + len as u32;
+        if self.next_addr > self.tail_addr {
+            // Free space after the newest frame, or before the oldest one
+            if end <= self.end_addr as u32 {
+                Some(self.next_addr)
+            } else if self.wrap_addr as u32 + len as u32 <= self.tail_addr as u32 {
+                Some(self.wrap_addr)
+            } else {
+                None
+            }
+        } else if end <= self.tail_addr as u32 {
+            // Already wrapped: free space between the newest and the oldest frame
+            Some(self.next_addr)
+        } else {
+            None
+        }
+    }
+
+    /// Stage a frame at an address returned by `allocate`
+    pub fn push(&mut self, start: u16, len: u16) {
+        if self.is_empty() {
+            self.tail_addr = start;
+        }
+        self.queue[(self.queue_head + self.queue_len) % TX_QUEUE_LENGTH] = (start, len);
+        self.queue_len += 1;
+        self.next_addr = start + len;
+    }
+
+    /// Start address and length of the oldest staged frame
+    pub fn front(&self) -> Option<(u16, u16)> {
+        if self.is_empty() { None } else { Some(self.queue[self.queue_head]) }
+    }
+
+    /// Release the oldest staged frame once it has been sent
+    pub fn pop(&mut self) -> Option<(u16, u16)> {
+        let frame = self.front()?;
+        self.queue_head = (self.queue_head + 1) % TX_QUEUE_LENGTH;
+        self.queue_len -= 1;
+        self.transmitting = false;
+        match self.front() {
+            Some((start, _)) => self.tail_addr = start,
+            None => self.reset(),
+        }
+        Some(frame)
+    }
+
+    /// Whether the oldest staged frame has been handed to the MAC (TXRTS set)
+    pub fn set_transmitting(&mut self, transmitting: bool) {
+        self.transmitting = transmitting;
+    }
+    pub fn is_transmitting(&self) -> bool {
+        self.transmitting
+    }
 }
 
 /// Struct for TX Packet
 /// TODO: Generalise MAC addresses
 pub struct TxPacket {
     frame: [u8; RAW_FRAME_LENGTH_MAX],
-    frame_length: usize
+    frame_length: usize,
+    checksum_offload: bool
+}
+
+impl Default for TxPacket {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TxPacket {
     pub fn new() -> Self {
         TxPacket {
             frame: [0; RAW_FRAME_LENGTH_MAX],
-            frame_length: 0
+            frame_length: 0,
+            checksum_offload: false
+        }
+    }
+
+    /// Build a broadcast Magic Packet that wakes the station at `target`
+    /// The payload is 6 bytes of 0xff followed by 16 repetitions of `target`.
+    pub fn magic_packet(target: MacAddress, source: MacAddress) -> Self {
+        let mut packet = TxPacket::new();
+        packet.frame[0..6].copy_from_slice(MacAddress::BROADCAST.as_bytes());
+        packet.frame[6..12].copy_from_slice(source.as_bytes());
+        packet.frame[12..14].copy_from_slice(&ETHERTYPE_WAKE_ON_LAN.to_be_bytes());
+        packet.frame[14..20].copy_from_slice(&[0xff; 6]);
+        for i in 0..16 {
+            let start = 20 + 6 * i;
+            packet.frame[start..start + 6].copy_from_slice(target.as_bytes());
         }
+        packet.frame_length = (20 + 6 * 16).max(MIN_FRAME_LENGTH);
+        packet
     }
 
     /// Currently, frame data is copied from an external buffer
     pub fn update_frame(&mut self, raw_frame: &[u8], raw_frame_length: usize) {
         self.frame_length = raw_frame_length;
-        for i in 0..self.frame_length {
-            self.frame[i] = raw_frame[i];
-        }
+        self.frame[..self.frame_length].copy_from_slice(&raw_frame[..self.frame_length]);
     }
     pub fn write_frame_to(&self, frame: &mut [u8]) {
-        for i in 0..self.frame_length {
-            frame[i] = self.frame[i];
-        }
+        frame[..self.frame_length].copy_from_slice(&self.frame[..self.frame_length]);
     }
 
     pub fn get_frame_length(&self) -> usize {
         self.frame_length
     }
 
+    /// Have the controller fill in the IPv4 header and TCP, UDP, ICMP or ICMPv6
+    /// checksums of the frame in SRAM with the DMA engine before sending it
+    pub fn set_checksum_offload(&mut self, offload: bool) {
+        self.checksum_offload = offload;
+    }
+    pub fn is_checksum_offload(&self) -> bool {
+        self.checksum_offload
+    }
+
     pub fn get_frame(&self) -> &[u8] {
         &self.frame
     }